@@ -1,16 +1,179 @@
-use crate::token::Position;
-use crate::utils::{Printer, ToSimpleString};
+use crate::diagnostic::Diagnostic;
+use crate::layout::X86_64_SYSV;
+use crate::token::{escape_bytes, Position};
+use crate::utils::ToSimpleString;
 use std::fmt;
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum Type {
+  Void,
+  Bool,
+  Char,
+  UChar,
+  Short,
+  UShort,
   Int,
+  UInt,
+  Long,
+  ULong,
+  LongLong,
+  ULongLong,
+  Pointer(Box<Type>),
+  // the element type and the length
+  Array(Box<Type>, usize),
+}
+
+impl Type {
+  /// Whether values of the type are unsigned. 'char' is signed on x86-64,
+  /// and addresses compare as unsigned.
+  pub fn is_unsigned(&self) -> bool {
+    match self {
+      Type::Bool
+      | Type::UChar
+      | Type::UShort
+      | Type::UInt
+      | Type::ULong
+      | Type::ULongLong
+      | Type::Pointer(_)
+      | Type::Array(..) => true,
+      Type::Void | Type::Char | Type::Short | Type::Int | Type::Long | Type::LongLong => false,
+    }
+  }
+
+  pub fn is_pointer(&self) -> bool {
+    matches!(self, Type::Pointer(_))
+  }
+
+  pub fn is_array(&self) -> bool {
+    matches!(self, Type::Array(..))
+  }
+
+  /// The type pointed to, if `self` is a pointer.
+  pub fn pointee(&self) -> Option<&Type> {
+    match self {
+      Type::Pointer(pointee) => Some(pointee),
+      _ => None,
+    }
+  }
+
+  /// Returns the type an expression of the type is converted to when its
+  /// value is used: an array becomes a pointer to its first element.
+  pub fn decayed(&self) -> Type {
+    match self {
+      Type::Array(elem, _) => Type::Pointer(elem.clone()),
+      _ => self.clone(),
+    }
+  }
+
+  /// Splits a possibly multi-dimensional array type into its innermost
+  /// element type and the '[len]'s, e.g. 'int' and '[2][3]'.
+  fn array_dims(&self) -> (&Type, String) {
+    let mut typ = self;
+    let mut dims = String::new();
+    while let Type::Array(elem, len) = typ {
+      dims += &format!("[{}]", len);
+      typ = elem;
+    }
+    (typ, dims)
+  }
+
+  /// The integer conversion rank, ordering types by their range.
+  fn rank(&self) -> usize {
+    match self {
+      Type::Void | Type::Bool => 0,
+      Type::Char | Type::UChar => 1,
+      Type::Short | Type::UShort => 2,
+      Type::Int | Type::UInt => 3,
+      Type::Long | Type::ULong | Type::Pointer(_) | Type::Array(..) => 4,
+      Type::LongLong | Type::ULongLong => 5,
+    }
+  }
+
+  fn to_unsigned(&self) -> Type {
+    match self {
+      Type::Bool
+      | Type::Char
+      | Type::UChar
+      | Type::Short
+      | Type::UShort
+      | Type::Int
+      | Type::UInt => Type::UInt,
+      Type::Long | Type::ULong => Type::ULong,
+      Type::LongLong | Type::ULongLong => Type::ULongLong,
+      Type::Void | Type::Pointer(_) | Type::Array(..) => self.clone(),
+    }
+  }
+
+  /// Returns the type after the integer promotions, which convert types
+  /// ranked below 'int' to 'int'.
+  pub fn promoted(&self) -> Type {
+    match self {
+      Type::Bool | Type::Char | Type::UChar | Type::Short | Type::UShort => Type::Int,
+      _ => self.clone(),
+    }
+  }
+
+  /// Returns the type the operands of an arithmetic operator are converted
+  /// to by the usual arithmetic conversions (C11 6.3.1.8). A pointer is only
+  /// compared with another pointer or a null pointer constant, so it wins.
+  pub fn common(&self, other: &Type) -> Type {
+    if self.is_pointer() {
+      return self.clone();
+    }
+    if other.is_pointer() {
+      return other.clone();
+    }
+    let (this, other) = (&self.promoted(), &other.promoted());
+    if this.is_unsigned() == other.is_unsigned() {
+      return if this.rank() >= other.rank() {
+        this.clone()
+      } else {
+        other.clone()
+      };
+    }
+    let (unsigned, signed) = if this.is_unsigned() {
+      (this, other)
+    } else {
+      (other, this)
+    };
+    if unsigned.rank() >= signed.rank() {
+      unsigned.clone()
+    } else if *unsigned == Type::UInt {
+      // 'long' and 'long long' are 64-bit, so they hold any 'unsigned int'
+      signed.clone()
+    } else {
+      signed.to_unsigned()
+    }
+  }
 }
 
 impl ToSimpleString for Type {
   fn to_simple_string(&self) -> String {
     match self {
+      Type::Void => String::from("void"),
+      Type::Bool => String::from("_Bool"),
+      Type::Char => String::from("char"),
+      Type::UChar => String::from("unsigned char"),
+      Type::Short => String::from("short"),
+      Type::UShort => String::from("unsigned short"),
       Type::Int => String::from("int"),
+      Type::UInt => String::from("unsigned int"),
+      Type::Long => String::from("long"),
+      Type::ULong => String::from("unsigned long"),
+      Type::LongLong => String::from("long long"),
+      Type::ULongLong => String::from("unsigned long long"),
+      // 'int *' but 'int **', and 'int (*)[3]' for a pointer to an array
+      Type::Pointer(pointee) if pointee.is_pointer() => format!("{}*", pointee),
+      Type::Pointer(pointee) if pointee.is_array() => {
+        let (elem, dims) = pointee.array_dims();
+        format!("{} (*){}", elem, dims)
+      }
+      Type::Pointer(pointee) => format!("{} *", pointee),
+      // 'int [3]' but 'int *[3]'
+      Type::Array(..) => match self.array_dims() {
+        (elem, dims) if elem.is_pointer() => format!("{}{}", elem, dims),
+        (elem, dims) => format!("{} {}", elem, dims),
+      },
     }
   }
 }
@@ -25,6 +188,14 @@ impl fmt::Display for Type {
 pub enum UnaryOpType {
   Plus,
   Minus,
+  Not,
+  BitNot,
+  PreInc,
+  PreDec,
+  PostInc,
+  PostDec,
+  AddrOf,
+  Deref,
 }
 
 impl ToSimpleString for UnaryOpType {
@@ -32,6 +203,14 @@ impl ToSimpleString for UnaryOpType {
     match self {
       UnaryOpType::Plus => String::from("Plus"),
       UnaryOpType::Minus => String::from("Minus"),
+      UnaryOpType::Not => String::from("Not"),
+      UnaryOpType::BitNot => String::from("BitNot"),
+      UnaryOpType::PreInc => String::from("PreInc"),
+      UnaryOpType::PreDec => String::from("PreDec"),
+      UnaryOpType::PostInc => String::from("PostInc"),
+      UnaryOpType::PostDec => String::from("PostDec"),
+      UnaryOpType::AddrOf => String::from("AddrOf"),
+      UnaryOpType::Deref => String::from("Deref"),
     }
   }
 }
@@ -42,21 +221,60 @@ impl fmt::Display for UnaryOpType {
   }
 }
 
+// an operator taking a type name, e.g. 'sizeof(int)'
+#[derive(Debug)]
+pub enum TypeOpType {
+  Sizeof,
+  Alignof,
+}
+
+impl ToSimpleString for TypeOpType {
+  fn to_simple_string(&self) -> String {
+    match self {
+      TypeOpType::Sizeof => String::from("Sizeof"),
+      TypeOpType::Alignof => String::from("Alignof"),
+    }
+  }
+}
+
+impl fmt::Display for TypeOpType {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    write!(f, "{}", self.to_simple_string())
+  }
+}
+
 #[derive(Debug)]
 pub enum BinaryOpType {
   Add,
   Sub,
   Mul,
   Div,
+  Mod,
+  BitAnd,
+  BitOr,
+  BitXor,
+  Shl,
+  Shr,
   Assign,
-  Inc,
-  Dec,
+  AddAssign,
+  SubAssign,
+  MulAssign,
+  DivAssign,
+  ModAssign,
+  ShlAssign,
+  ShrAssign,
+  AndAssign,
+  OrAssign,
+  XorAssign,
   Eq,
   Ne,
   Lt,
   Le,
   Gt,
   Ge,
+  LogicalAnd,
+  LogicalOr,
+  Comma,
 }
 
 impl ToSimpleString for BinaryOpType {
@@ -66,15 +284,91 @@ impl ToSimpleString for BinaryOpType {
       BinaryOpType::Sub => String::from("Sub"),
       BinaryOpType::Mul => String::from("Mul"),
       BinaryOpType::Div => String::from("Div"),
+      BinaryOpType::Mod => String::from("Mod"),
+      BinaryOpType::BitAnd => String::from("BitAnd"),
+      BinaryOpType::BitOr => String::from("BitOr"),
+      BinaryOpType::BitXor => String::from("BitXor"),
+      BinaryOpType::Shl => String::from("Shl"),
+      BinaryOpType::Shr => String::from("Shr"),
       BinaryOpType::Assign => String::from("Assign"),
-      BinaryOpType::Inc => String::from("Inc"),
-      BinaryOpType::Dec => String::from("Dec"),
+      BinaryOpType::AddAssign => String::from("AddAssign"),
+      BinaryOpType::SubAssign => String::from("SubAssign"),
+      BinaryOpType::MulAssign => String::from("MulAssign"),
+      BinaryOpType::DivAssign => String::from("DivAssign"),
+      BinaryOpType::ModAssign => String::from("ModAssign"),
+      BinaryOpType::ShlAssign => String::from("ShlAssign"),
+      BinaryOpType::ShrAssign => String::from("ShrAssign"),
+      BinaryOpType::AndAssign => String::from("AndAssign"),
+      BinaryOpType::OrAssign => String::from("OrAssign"),
+      BinaryOpType::XorAssign => String::from("XorAssign"),
       BinaryOpType::Eq => String::from("Eq"),
       BinaryOpType::Ne => String::from("Ne"),
       BinaryOpType::Lt => String::from("Lt"),
       BinaryOpType::Le => String::from("Le"),
       BinaryOpType::Gt => String::from("Gt"),
       BinaryOpType::Ge => String::from("Ge"),
+      BinaryOpType::LogicalAnd => String::from("LogicalAnd"),
+      BinaryOpType::LogicalOr => String::from("LogicalOr"),
+      BinaryOpType::Comma => String::from("Comma"),
+    }
+  }
+}
+
+impl BinaryOpType {
+  /// Returns the operator applied by a compound assignment, e.g. `Add` for
+  /// `+=`.
+  pub fn compound_op(&self) -> Option<BinaryOpType> {
+    let op = match self {
+      BinaryOpType::AddAssign => BinaryOpType::Add,
+      BinaryOpType::SubAssign => BinaryOpType::Sub,
+      BinaryOpType::MulAssign => BinaryOpType::Mul,
+      BinaryOpType::DivAssign => BinaryOpType::Div,
+      BinaryOpType::ModAssign => BinaryOpType::Mod,
+      BinaryOpType::ShlAssign => BinaryOpType::Shl,
+      BinaryOpType::ShrAssign => BinaryOpType::Shr,
+      BinaryOpType::AndAssign => BinaryOpType::BitAnd,
+      BinaryOpType::OrAssign => BinaryOpType::BitOr,
+      BinaryOpType::XorAssign => BinaryOpType::BitXor,
+      _ => return None,
+    };
+    Some(op)
+  }
+
+  /// Returns the type of the result of `self` on operands of the decayed
+  /// types `lhs` and `rhs`.
+  pub fn result_type(&self, lhs: &Type, rhs: &Type) -> Type {
+    match self {
+      // the difference of two pointers is a 'ptrdiff_t'
+      BinaryOpType::Sub if lhs.is_pointer() && rhs.is_pointer() => Type::Long,
+      BinaryOpType::Add
+      | BinaryOpType::Sub
+      | BinaryOpType::Mul
+      | BinaryOpType::Div
+      | BinaryOpType::Mod
+      | BinaryOpType::BitAnd
+      | BinaryOpType::BitOr
+      | BinaryOpType::BitXor => lhs.common(rhs),
+      BinaryOpType::Shl | BinaryOpType::Shr => lhs.promoted(),
+      BinaryOpType::Assign
+      | BinaryOpType::AddAssign
+      | BinaryOpType::SubAssign
+      | BinaryOpType::MulAssign
+      | BinaryOpType::DivAssign
+      | BinaryOpType::ModAssign
+      | BinaryOpType::ShlAssign
+      | BinaryOpType::ShrAssign
+      | BinaryOpType::AndAssign
+      | BinaryOpType::OrAssign
+      | BinaryOpType::XorAssign => lhs.clone(),
+      BinaryOpType::Comma => rhs.clone(),
+      BinaryOpType::Eq
+      | BinaryOpType::Ne
+      | BinaryOpType::Lt
+      | BinaryOpType::Le
+      | BinaryOpType::Gt
+      | BinaryOpType::Ge
+      | BinaryOpType::LogicalAnd
+      | BinaryOpType::LogicalOr => Type::Int,
     }
   }
 }
@@ -93,11 +387,23 @@ pub enum Expr {
   },
   Number {
     value: u64,
+    typ: Type,
+    position: Position,
+  },
+  // a character constant, which has type 'int' in C
+  Char {
+    value: u8,
+    position: Position,
+  },
+  // a string literal with adjacent ones concatenated, which is a 'char' array
+  // decaying into 'char *'; 'value' doesn't have the trailing NUL
+  Str {
+    value: Vec<u8>,
     position: Position,
   },
   Call {
     name: String,
-    args: Vec<Box<Expr>>,
+    args: Vec<Expr>,
     position: Position,
   },
   UnaryOp {
@@ -111,13 +417,44 @@ pub enum Expr {
     rhs: Box<Expr>,
     position: Position,
   },
+  TypeOp {
+    op: TypeOpType,
+    typ: Type,
+    position: Position,
+  },
+  // 'cond ? then_expr : else_expr'
+  Conditional {
+    cond: Box<Expr>,
+    then_expr: Box<Expr>,
+    else_expr: Box<Expr>,
+    position: Position,
+  },
 }
 
 impl ToSimpleString for Expr {
   fn to_simple_string(&self) -> String {
     match self {
       Expr::Id { name, position } => format!("Id{}{{'{}'}}", position, name),
-      Expr::Number { value, position } => format!("Num{}{{{}}}", position, value),
+      Expr::Number {
+        value,
+        typ: Type::Int,
+        position,
+      } => format!("Num{}{{{}}}", position, value),
+      Expr::Number {
+        value,
+        typ,
+        position,
+      } => format!("Num{}{{{}: {}}}", position, value, typ),
+      Expr::Char { value, position } => {
+        format!(
+          "Char{}{{'{}'}}",
+          position,
+          (*value as char).escape_default()
+        )
+      }
+      Expr::Str { value, position } => {
+        format!("Str{}{{\"{}\"}}", position, escape_bytes(value))
+      }
       Expr::UnaryOp { op, rhs, position } => format!("{}{}{{{}}}", op, position, rhs),
       Expr::BinaryOp {
         op,
@@ -125,6 +462,16 @@ impl ToSimpleString for Expr {
         lhs,
         position,
       } => format!("{}{}{{{}, {}}}", op, position, lhs, rhs),
+      Expr::TypeOp { op, typ, position } => format!("{}{}{{{}}}", op, position, typ),
+      Expr::Conditional {
+        cond,
+        then_expr,
+        else_expr,
+        position,
+      } => format!(
+        "Conditional{}{{{}, {}, {}}}",
+        position, cond, then_expr, else_expr
+      ),
       Expr::Call {
         name,
         args,
@@ -132,17 +479,165 @@ impl ToSimpleString for Expr {
       } => {
         let mut s = format!("Call{}{{'{}',", position, name);
         for (i, arg) in args.iter().enumerate() {
-          s.push_str("\n");
-          s.push_str(&format!("  {}: ", 1));
+          s.push('\n');
+          s.push_str(&format!("  {}: ", i));
           s.push_str(&arg.to_simple_string());
         }
-        s.push_str("\n}}");
+        s.push_str("\n}");
         s
       }
     }
   }
 }
 
+/// The declarations in scope where an expression is typed.
+pub trait Symbols {
+  /// Returns the type of the variable `name`, or `None` if it isn't declared.
+  fn var_type(&self, name: &str) -> Option<Type>;
+  /// Returns the return type of the function `name`, or `None` if it isn't
+  /// declared.
+  fn ret_type(&self, name: &str) -> Option<Type>;
+}
+
+impl Expr {
+  /// Evaluates `self` as an integer constant expression, or returns `None`
+  /// if it isn't one.
+  pub fn const_value(&self) -> Option<i64> {
+    match self {
+      Expr::Number { value, .. } => Some(*value as i64),
+      Expr::Char { value, .. } => Some(*value as i8 as i64),
+      Expr::TypeOp { op, typ, .. } => match op {
+        TypeOpType::Sizeof => Some(X86_64_SYSV.size_of(typ) as i64),
+        TypeOpType::Alignof => Some(X86_64_SYSV.align_of(typ) as i64),
+      },
+      Expr::UnaryOp { op, rhs, .. } => {
+        let value = rhs.const_value()?;
+        match op {
+          UnaryOpType::Plus => Some(value),
+          UnaryOpType::Minus => Some(value.wrapping_neg()),
+          UnaryOpType::Not => Some((value == 0) as i64),
+          UnaryOpType::BitNot => Some(!value),
+          UnaryOpType::PreInc
+          | UnaryOpType::PreDec
+          | UnaryOpType::PostInc
+          | UnaryOpType::PostDec
+          | UnaryOpType::AddrOf
+          | UnaryOpType::Deref => None,
+        }
+      }
+      Expr::BinaryOp { op, lhs, rhs, .. } => {
+        let lhs = lhs.const_value()?;
+        let rhs = rhs.const_value()?;
+        match op {
+          BinaryOpType::Add => Some(lhs.wrapping_add(rhs)),
+          BinaryOpType::Sub => Some(lhs.wrapping_sub(rhs)),
+          BinaryOpType::Mul => Some(lhs.wrapping_mul(rhs)),
+          BinaryOpType::Div => lhs.checked_div(rhs),
+          BinaryOpType::Mod => lhs.checked_rem(rhs),
+          BinaryOpType::BitAnd => Some(lhs & rhs),
+          BinaryOpType::BitOr => Some(lhs | rhs),
+          BinaryOpType::BitXor => Some(lhs ^ rhs),
+          // shifting by a negative or too large amount is undefined
+          BinaryOpType::Shl if (0..64).contains(&rhs) => Some(lhs << rhs),
+          BinaryOpType::Shr if (0..64).contains(&rhs) => Some(lhs >> rhs),
+          BinaryOpType::Shl | BinaryOpType::Shr => None,
+          BinaryOpType::Eq => Some((lhs == rhs) as i64),
+          BinaryOpType::Ne => Some((lhs != rhs) as i64),
+          BinaryOpType::Lt => Some((lhs < rhs) as i64),
+          BinaryOpType::Le => Some((lhs <= rhs) as i64),
+          BinaryOpType::Gt => Some((lhs > rhs) as i64),
+          BinaryOpType::Ge => Some((lhs >= rhs) as i64),
+          BinaryOpType::LogicalAnd => Some((lhs != 0 && rhs != 0) as i64),
+          BinaryOpType::LogicalOr => Some((lhs != 0 || rhs != 0) as i64),
+          BinaryOpType::Comma => None,
+          BinaryOpType::Assign
+          | BinaryOpType::AddAssign
+          | BinaryOpType::SubAssign
+          | BinaryOpType::MulAssign
+          | BinaryOpType::DivAssign
+          | BinaryOpType::ModAssign
+          | BinaryOpType::ShlAssign
+          | BinaryOpType::ShrAssign
+          | BinaryOpType::AndAssign
+          | BinaryOpType::OrAssign
+          | BinaryOpType::XorAssign => None,
+        }
+      }
+      Expr::Conditional {
+        cond,
+        then_expr,
+        else_expr,
+        ..
+      } => {
+        if cond.const_value()? != 0 {
+          then_expr.const_value()
+        } else {
+          else_expr.const_value()
+        }
+      }
+      Expr::Id { .. } | Expr::Str { .. } | Expr::Call { .. } => None,
+    }
+  }
+
+  /// Returns the type of `self`, looking up the names in it in `symbols`.
+  /// A call to an undeclared function is taken as returning 'int'.
+  pub fn typ(&self, symbols: &dyn Symbols) -> Result<Type, Diagnostic> {
+    Ok(match self {
+      Expr::Number { typ, .. } => typ.clone(),
+      Expr::Str { value, .. } => Type::Array(Box::new(Type::Char), value.len() + 1),
+      // 'size_t'
+      Expr::TypeOp { .. } => Type::ULong,
+      // a character constant is an 'int' in C
+      Expr::Char { .. } => Type::Int,
+      Expr::Id { name, position } => symbols.var_type(name).ok_or_else(|| {
+        Diagnostic::error(
+          format!("use of undeclared identifier '{}'", name),
+          *position,
+        )
+      })?,
+      Expr::Call { name, .. } => symbols.ret_type(name).unwrap_or(Type::Int),
+      Expr::Conditional {
+        then_expr,
+        else_expr,
+        ..
+      } => then_expr
+        .typ(symbols)?
+        .decayed()
+        .common(&else_expr.typ(symbols)?.decayed()),
+      Expr::UnaryOp { op, rhs, .. } => {
+        let rhs_type = rhs.typ(symbols)?;
+        match op {
+          UnaryOpType::Not => Type::Int,
+          UnaryOpType::Plus | UnaryOpType::Minus | UnaryOpType::BitNot => rhs_type.promoted(),
+          UnaryOpType::PreInc
+          | UnaryOpType::PreDec
+          | UnaryOpType::PostInc
+          | UnaryOpType::PostDec => rhs_type,
+          UnaryOpType::AddrOf => Type::Pointer(Box::new(rhs_type)),
+          UnaryOpType::Deref => rhs_type.decayed().pointee().cloned().unwrap_or(Type::Int),
+        }
+      }
+      Expr::BinaryOp { op, lhs, rhs, .. } => {
+        op.result_type(&lhs.typ(symbols)?.decayed(), &rhs.typ(symbols)?.decayed())
+      }
+    })
+  }
+
+  pub fn position(&self) -> Position {
+    match self {
+      Expr::Id { position, .. }
+      | Expr::Number { position, .. }
+      | Expr::Char { position, .. }
+      | Expr::Str { position, .. }
+      | Expr::Call { position, .. }
+      | Expr::UnaryOp { position, .. }
+      | Expr::BinaryOp { position, .. }
+      | Expr::TypeOp { position, .. }
+      | Expr::Conditional { position, .. } => *position,
+    }
+  }
+}
+
 impl fmt::Display for Expr {
   fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
     write!(f, "{}", self.to_simple_string())
@@ -151,12 +646,17 @@ impl fmt::Display for Expr {
 
 #[derive(Debug, Clone)]
 pub struct Arg {
+  // empty for an unnamed parameter of a prototype
   pub name: String,
   pub typ: Type,
+  pub position: Position,
 }
 impl ToSimpleString for Arg {
   fn to_simple_string(&self) -> String {
-    format!("'{}'", self.name)
+    if self.name.is_empty() {
+      return self.typ.to_simple_string();
+    }
+    format!("'{}': {}", self.name, self.typ)
   }
 }
 impl fmt::Display for Arg {
@@ -165,19 +665,54 @@ impl fmt::Display for Arg {
   }
 }
 
+/// A variable declared by `Stmt::VarDecl`, e.g. `b = 2` in `int a, b = 2;`.
+#[derive(Debug)]
+pub struct Declarator {
+  pub name: String,
+  pub typ: Type,
+  pub init: Option<Box<Expr>>,
+  pub position: Position,
+}
+impl ToSimpleString for Declarator {
+  fn to_simple_string(&self) -> String {
+    match &self.init {
+      Some(init) => format!("'{}': {} = {}", self.name, self.typ, init),
+      None => format!("'{}': {}", self.name, self.typ),
+    }
+  }
+}
+impl fmt::Display for Declarator {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    write!(f, "{}", self.to_simple_string())
+  }
+}
+
 #[derive(Debug)]
 pub struct ArgList {
   pub container: Vec<Arg>,
-  index: usize,
+  // whether the list ends with '...'
+  pub variadic: bool,
 }
 impl ArgList {
   pub fn new() -> Self {
     ArgList {
       container: Vec::new(),
-      index: 0,
+      variadic: false,
     }
   }
 
+  /// Whether a function declared with `self` has the same parameter types as
+  /// one declared with `other`; the names don't matter.
+  pub fn same_types(&self, other: &ArgList) -> bool {
+    self.variadic == other.variadic
+      && self.container.len() == other.container.len()
+      && self
+        .container
+        .iter()
+        .zip(other.container.iter())
+        .all(|(a, b)| a.typ == b.typ)
+  }
+
   // pub fn len(&self) -> usize {
   //   self.container.len()
   // }
@@ -196,7 +731,10 @@ impl ToSimpleString for ArgList {
       }
       args_str.push_str(&arg.to_simple_string());
     }
-    args_str.push_str("]");
+    if self.variadic {
+      args_str.push_str(", ...");
+    }
+    args_str.push(']');
     args_str
   }
 }
@@ -207,29 +745,101 @@ impl fmt::Display for ArgList {
 }
 
 #[derive(Debug)]
+#[allow(clippy::enum_variant_names)]
 pub enum Stmt {
   ExprStmt {
     expr: Box<Expr>,
   },
+  // 'int a = 1, b;', whose variables are visible up to the end of the block
+  VarDecl {
+    decls: Vec<Declarator>,
+    // only at file scope, where it declares variables defined elsewhere
+    is_extern: bool,
+    position: Position,
+  },
+  // '{ ... }', which opens a new scope
+  Block {
+    body: Vec<Stmt>,
+    position: Position,
+  },
+  // a lone ';'
+  Empty {
+    position: Position,
+  },
   IfStmt {
     cond: Box<Expr>,
-    true_body: Vec<Box<Stmt>>,
-    false_body: Option<Vec<Box<Stmt>>>,
+    true_body: Box<Stmt>,
+    false_body: Option<Box<Stmt>>,
+    position: Position,
   },
   ForStmt {
     cond: Option<Box<Expr>>,
     prologue: Option<Box<Expr>>,
     epilogue: Option<Box<Expr>>,
-    body: Vec<Box<Stmt>>,
+    body: Box<Stmt>,
+    position: Position,
+  },
+  WhileStmt {
+    cond: Box<Expr>,
+    body: Box<Stmt>,
+    position: Position,
+  },
+  DoWhileStmt {
+    body: Box<Stmt>,
+    cond: Box<Expr>,
+    position: Position,
+  },
+  SwitchStmt {
+    cond: Box<Expr>,
+    // the promoted type of 'cond', which the 'case' values are converted to
+    typ: Type,
+    body: Box<Stmt>,
+    position: Position,
+  },
+  // 'case' and 'default' label the statement after them, and falling through
+  // to the next label is just moving on past the end of 'body'
+  CaseStmt {
+    value: Box<Expr>,
+    body: Box<Stmt>,
+    position: Position,
+  },
+  DefaultStmt {
+    body: Box<Stmt>,
+    position: Position,
+  },
+  // a label like 'case', jumped to by 'goto' in the same function
+  LabelStmt {
+    name: String,
+    body: Box<Stmt>,
+    position: Position,
+  },
+  GotoStmt {
+    name: String,
+    position: Position,
+  },
+  BreakStmt {
+    position: Position,
+  },
+  ContinueStmt {
+    position: Position,
   },
   ReturnStmt {
     expr: Option<Box<Expr>>,
+    position: Position,
   },
   FnStmt {
     name: String,
     args: ArgList,
-    body: Vec<Box<Stmt>>,
+    body: Vec<Stmt>,
     ret_type: Type,
+    position: Position,
+  },
+  // a prototype, i.e. a function declaration without a body
+  FnDecl {
+    name: String,
+    args: ArgList,
+    ret_type: Type,
+    position: Position,
   },
 }
 
@@ -237,29 +847,35 @@ impl ToSimpleString for Stmt {
   fn to_simple_string(&self) -> String {
     match self {
       Stmt::ExprStmt { expr } => format!("Stmt({})", expr),
+      Stmt::VarDecl {
+        decls, is_extern, ..
+      } => {
+        let decls: Vec<String> = decls.iter().map(|decl| decl.to_simple_string()).collect();
+        let storage = if *is_extern { "extern " } else { "" };
+        format!("VarDecl({}{})", storage, decls.join(", "))
+      }
+      Stmt::Block { body, .. } => {
+        let mut s = String::from("{\n");
+        s += &stmt_list_to_string(body);
+        s.push('}');
+        s
+      }
+      Stmt::Empty { .. } => String::from("Empty"),
       Stmt::IfStmt {
         cond,
         true_body,
         false_body,
-      } => {
-        let mut s = format!("If({}) {{\n", cond);
-        s += &stmt_list_to_string(true_body);
-        s.push_str("}");
-        match false_body {
-          Some(body) => {
-            s.push_str(" Else {\n");
-            s += &stmt_list_to_string(body);
-            s.push_str("}");
-            s
-          }
-          None => s,
-        }
-      }
+        ..
+      } => match false_body {
+        Some(body) => format!("If({}) {} Else {}", cond, true_body, body),
+        None => format!("If({}) {}", cond, true_body),
+      },
       Stmt::ForStmt {
         cond,
         prologue,
         epilogue,
         body,
+        ..
       } => {
         let mut s = String::from("for(\n");
         if let Some(expr) = prologue {
@@ -269,35 +885,74 @@ impl ToSimpleString for Stmt {
           s.push_str(&format!("{},", expr));
         }
         if let Some(expr) = epilogue {
-          s.push_str(&format!("{}", expr));
+          s.push_str(&expr.to_simple_string());
         }
-        s.push_str(") {");
-        s += &stmt_list_to_string(body);
-        s.push_str("}");
+        s.push_str(&format!(") {}", body));
         s
       }
-      Stmt::ReturnStmt { expr } => match expr {
+      Stmt::WhileStmt { cond, body, .. } => format!("While({}) {}", cond, body),
+      Stmt::DoWhileStmt { body, cond, .. } => format!("Do {} While({})", body, cond),
+      Stmt::SwitchStmt { cond, body, .. } => format!("Switch({}) {}", cond, body),
+      Stmt::CaseStmt { value, body, .. } => format!("Case({}) {}", value, body),
+      Stmt::DefaultStmt { body, .. } => format!("Default {}", body),
+      Stmt::LabelStmt { name, body, .. } => format!("Label('{}') {}", name, body),
+      Stmt::GotoStmt { name, .. } => format!("Goto('{}')", name),
+      Stmt::BreakStmt { .. } => String::from("Break"),
+      Stmt::ContinueStmt { .. } => String::from("Continue"),
+      Stmt::ReturnStmt { expr, .. } => match expr {
         Some(expr) => format!("Return({})", expr),
-        None => format!("Return()"),
+        None => String::from("Return()"),
       },
       Stmt::FnStmt {
         name,
         args,
         body,
         ret_type,
+        ..
       } => {
         let mut fn_str = format!("Fn({}, {}) -> {} {{", name, args, ret_type);
         for (i, stmt) in body.iter().enumerate() {
-          fn_str.push_str("\n");
+          fn_str.push('\n');
           fn_str.push_str(&format!("  {}: ", i));
           fn_str.push_str(&stmt.to_simple_string());
         }
-        fn_str.push_str("}");
+        fn_str.push('}');
         fn_str
       }
+      Stmt::FnDecl {
+        name,
+        args,
+        ret_type,
+        ..
+      } => format!("FnDecl({}, {}) -> {}", name, args, ret_type),
     }
   }
 }
+impl Stmt {
+  pub fn position(&self) -> Position {
+    match self {
+      Stmt::ExprStmt { expr } => expr.position(),
+      Stmt::VarDecl { position, .. }
+      | Stmt::Block { position, .. }
+      | Stmt::Empty { position }
+      | Stmt::IfStmt { position, .. }
+      | Stmt::ForStmt { position, .. }
+      | Stmt::WhileStmt { position, .. }
+      | Stmt::DoWhileStmt { position, .. }
+      | Stmt::SwitchStmt { position, .. }
+      | Stmt::CaseStmt { position, .. }
+      | Stmt::DefaultStmt { position, .. }
+      | Stmt::LabelStmt { position, .. }
+      | Stmt::GotoStmt { position, .. }
+      | Stmt::BreakStmt { position, .. }
+      | Stmt::ContinueStmt { position, .. }
+      | Stmt::ReturnStmt { position, .. }
+      | Stmt::FnStmt { position, .. }
+      | Stmt::FnDecl { position, .. } => *position,
+    }
+  }
+}
+
 impl fmt::Display for Stmt {
   fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
     write!(f, "{}", self.to_simple_string())
@@ -306,7 +961,7 @@ impl fmt::Display for Stmt {
 
 #[derive(Debug)]
 pub struct Module {
-  pub stmt_list: Vec<Box<Stmt>>,
+  pub stmt_list: Vec<Stmt>,
 }
 
 impl Module {
@@ -316,7 +971,7 @@ impl Module {
     }
   }
 
-  pub fn add_stmt(&mut self, stmt: Box<Stmt>) {
+  pub fn add_stmt(&mut self, stmt: Stmt) {
     self.stmt_list.push(stmt);
   }
 }
@@ -325,7 +980,7 @@ impl ToSimpleString for Module {
   fn to_simple_string(&self) -> String {
     let mut s = String::from("Module {\n");
     s += &stmt_list_to_string(&self.stmt_list);
-    s.push_str("}");
+    s.push('}');
     s
   }
 }
@@ -335,12 +990,12 @@ impl fmt::Display for Module {
   }
 }
 
-fn stmt_list_to_string(stmt_list: &Vec<Box<Stmt>>) -> String {
+fn stmt_list_to_string(stmt_list: &[Stmt]) -> String {
   let mut s = String::new();
   for (i, stmt) in stmt_list.iter().enumerate() {
     s.push_str(&format!("  {}: ", i));
     s.push_str(&stmt.to_simple_string());
     s.push('\n');
   }
-  return s;
+  s
 }
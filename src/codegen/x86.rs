@@ -1,34 +1,105 @@
 use crate::ast::*;
+use crate::diagnostic::Diagnostic;
+use crate::layout::{DataLayout, X86_64_SYSV};
+use crate::token::Position;
 use std::collections::HashMap;
 use std::fs::File;
 use std::io::prelude::*;
 use std::io::BufWriter;
 use std::path::Path;
 
+// a switch is lowered to a jump table when it has at least this many cases
+// and its value range is at most `JUMP_TABLE_MAX_RANGE_RATIO` times their count
+const JUMP_TABLE_MIN_CASES: usize = 4;
+const JUMP_TABLE_MAX_RANGE_RATIO: usize = 3;
+
+/// A local variable, at `offset` bytes below 'rbp'.
+struct Var {
+  offset: usize,
+  typ: Type,
+  position: Position,
+}
+
+/// A variable at file scope, emitted into '.data' when it has a nonzero
+/// initializer and into '.bss' otherwise.
+#[derive(Clone)]
+struct Global {
+  name: String,
+  typ: Type,
+  init: Option<i64>,
+  // false while it has only been declared 'extern', which leaves the
+  // definition to another translation unit
+  defined: bool,
+  position: Position,
+}
+
 struct Env {
-  sym_table: HashMap<String, usize>,
-  index: usize,
+  // the variables declared in each nested scope, innermost last
+  scopes: Vec<HashMap<String, Var>>,
+  // the bytes below 'rbp' taken by the variables so far
+  stack_size: usize,
+  layout: &'static DataLayout,
 }
 impl Env {
-  fn new() -> Self {
+  fn new(layout: &'static DataLayout) -> Self {
     Env {
-      sym_table: HashMap::new(),
-      index: 0,
+      scopes: vec![HashMap::new()],
+      stack_size: 0,
+      layout,
     }
   }
 
-  fn alloc(&mut self, var_name: &str) -> usize {
-    let offset = (self.index + 1) * 8;
-    self.sym_table.insert(String::from(var_name), offset);
-    self.index += 1;
-    offset
+  fn enter_scope(&mut self) {
+    self.scopes.push(HashMap::new());
   }
 
-  fn get_offset(&mut self, var_name: &str) -> Option<usize> {
-    match self.sym_table.get(var_name) {
-      Some(offset) => Some(offset.clone()),
-      None => None,
+  fn leave_scope(&mut self) {
+    self.scopes.pop();
+  }
+
+  /// Allocates the bytes of `var_name` declared at `position` in the
+  /// innermost scope. Those of closed scopes aren't reused, so the frame just
+  /// grows with each variable.
+  fn alloc(&mut self, var_name: &str, typ: &Type, position: Position) -> Result<usize, Diagnostic> {
+    if *typ == Type::Void {
+      return Err(void_variable_error(position));
     }
+    let scope = self.scopes.last_mut().unwrap();
+    if let Some(previous) = scope.get(var_name) {
+      return Err(redefinition_error(var_name, position, previous.position));
+    }
+    // the variable takes the bytes right below those in use, moved down to
+    // its alignment. The offsets are 32-bit displacements from 'rbp'.
+    let layout = self.layout.layout(typ);
+    let offset = self
+      .stack_size
+      .checked_add(layout.size)
+      .map(|end| end.next_multiple_of(layout.align))
+      .filter(|offset| *offset <= i32::MAX as usize)
+      .ok_or_else(|| Diagnostic::error("total size of local variables is too large", position))?;
+    scope.insert(
+      String::from(var_name),
+      Var {
+        offset,
+        typ: typ.clone(),
+        position,
+      },
+    );
+    self.stack_size = offset;
+    Ok(offset)
+  }
+
+  /// The stack size taken by the variables, kept 16-byte aligned for calls.
+  fn frame_size(&self) -> usize {
+    self.stack_size.next_multiple_of(16)
+  }
+
+  fn get(&self, var_name: &str) -> Option<&Var> {
+    self
+      .scopes
+      .iter()
+      .rev()
+      .find_map(|scope| scope.get(var_name))
   }
 
   // fn allocated(&mut self, var_name: &str) -> bool {
@@ -40,11 +111,63 @@ impl Env {
   // }
 }
 
+/// The variables of `env` over the globals and functions of `codegen`, which
+/// expressions are typed in.
+struct Scope<'a> {
+  codegen: &'a Codegen,
+  env: &'a Env,
+}
+
+impl Symbols for Scope<'_> {
+  fn var_type(&self, name: &str) -> Option<Type> {
+    match self.env.get(name) {
+      Some(var) => Some(var.typ.clone()),
+      None => self
+        .codegen
+        .globals
+        .iter()
+        .find(|global| global.name == name)
+        .map(|global| global.typ.clone()),
+    }
+  }
+
+  fn ret_type(&self, name: &str) -> Option<Type> {
+    self
+      .codegen
+      .functions
+      .get(name)
+      .map(|(ret_type, ..)| ret_type.clone())
+  }
+}
+
 pub struct Codegen {
   pub code_list: Vec<String>,
   indent: usize,
   label_index: usize,
   rsp_count: i64,
+  // string literals emitted as '.LC{index}' into '.rodata'
+  string_pool: Vec<Vec<u8>>,
+  // switch jump tables emitted into '.rodata', as the table label and the
+  // target label of each entry
+  jump_tables: Vec<(String, Vec<String>)>,
+  // jump targets of 'break' and 'continue' for the enclosing statements
+  break_labels: Vec<String>,
+  continue_labels: Vec<String>,
+  // labels of the 'case'/'default' markers not generated yet in the
+  // enclosing switches, the next one last
+  case_labels: Vec<Vec<String>>,
+  // 'label_index' taken by the current function, which makes its 'goto'
+  // labels unique in the module
+  fn_label_index: usize,
+  // file-scope variables declared so far, visible to the functions after them
+  globals: Vec<Global>,
+  // the return types, parameter types and first declarations of the
+  // functions declared so far
+  functions: HashMap<String, (Type, Vec<Type>, Position)>,
+  // the return type of the function being generated
+  ret_type: Type,
+  // the sizes and alignments of the types on the target
+  layout: &'static DataLayout,
 }
 
 impl Codegen {
@@ -54,80 +177,284 @@ impl Codegen {
       indent: 0,
       label_index: 0,
       rsp_count: 0,
+      string_pool: Vec::new(),
+      jump_tables: Vec::new(),
+      break_labels: Vec::new(),
+      continue_labels: Vec::new(),
+      case_labels: Vec::new(),
+      fn_label_index: 0,
+      globals: Vec::new(),
+      functions: HashMap::new(),
+      ret_type: Type::Int,
+      layout: &X86_64_SYSV,
     }
   }
 
-  pub fn run(&mut self, module: Box<Module>) {
-    self.gen_module(module);
+  pub fn run(&mut self, module: Module) -> Result<(), Diagnostic> {
+    self.gen_module(module)
   }
 
-  pub fn export(&mut self, file_path: &Path, module: Box<Module>) {
-    self.run(module);
-    let out_file = File::create(file_path).expect(&format!(
-      "file create failed: {}",
-      file_path.to_string_lossy()
-    ));
+  /// Writes the code generated by `run` to `file_path`.
+  pub fn export(&self, file_path: &Path) -> std::io::Result<()> {
+    let out_file = File::create(file_path)?;
     let mut writer = BufWriter::new(out_file);
     for line in &self.code_list {
-      writer
-        .write_all(line.as_bytes())
-        .expect(&format!("Code line write failed at {}", &line));
+      writer.write_all(line.as_bytes())?;
     }
-    writer.flush().expect(&format!(
-      "Code export failed to {}",
-      file_path.to_string_lossy()
-    ))
+    writer.flush()
   }
 
-  fn gen_module(&mut self, module: Box<Module>) {
+  fn gen_module(&mut self, module: Module) -> Result<(), Diagnostic> {
     self.gen_module_prolouge();
 
     for stmt in module.stmt_list {
-      match *stmt {
+      match stmt {
         Stmt::FnStmt {
-          name, args, body, ..
+          name,
+          args,
+          body,
+          ret_type,
+          position,
         } => {
-          self.gen_fn(&name, args, body);
+          // a function may call itself
+          self.declare_function(&name, ret_type.clone(), &args, position)?;
+          self.ret_type = ret_type;
+          self.gen_fn(&name, args, body)?;
           self.set_newline();
         }
-        _ => panic!("currently FnStmt is only supported..."),
+        Stmt::VarDecl {
+          decls, is_extern, ..
+        } => {
+          for decl in decls {
+            self.declare_global(decl, is_extern)?;
+          }
+        }
+        // calls are resolved by the linker, so prototypes need no code
+        Stmt::FnDecl {
+          name,
+          args,
+          ret_type,
+          position,
+        } => self.declare_function(&name, ret_type, &args, position)?,
+        _ => {
+          return Err(Diagnostic::error(
+            "expected function definition at file scope",
+            stmt.position(),
+          ))
+        }
       }
     }
+
+    self.gen_rodata();
+    self.gen_data();
+    Ok(())
+  }
+
+  /// Records a file-scope variable. A declaration without an initializer is
+  /// only tentative, so a variable may be declared any number of times but
+  /// initialized once.
+  fn declare_global(&mut self, decl: Declarator, is_extern: bool) -> Result<(), Diagnostic> {
+    if decl.typ.is_array() && decl.init.is_some() {
+      return Err(array_init_error(decl.position));
+    }
+    if decl.typ == Type::Void {
+      return Err(void_variable_error(decl.position));
+    }
+    if let Some((.., previous)) = self.functions.get(&decl.name) {
+      return Err(kind_redefinition_error(
+        &decl.name,
+        decl.position,
+        *previous,
+      ));
+    }
+    // the validator rejects non-constant initializers before codegen, and
+    // the value is converted to the type as if it were stored
+    let init = decl.init.as_ref().map(|init| {
+      let value = init.const_value().expect("non-constant initializer");
+      self.layout.convert(value, &decl.typ)
+    });
+    // an 'extern' declaration with an initializer is still a definition
+    let defined = !is_extern || init.is_some();
+    match self
+      .globals
+      .iter_mut()
+      .find(|global| global.name == decl.name)
+    {
+      Some(global) if global.typ != decl.typ => {
+        return Err(
+          Diagnostic::error(
+            format!(
+              "redefinition of '{}' with a different type: '{}' vs '{}'",
+              decl.name, decl.typ, global.typ
+            ),
+            decl.position,
+          )
+          .with_label(global.position, "previous definition is here"),
+        )
+      }
+      Some(global) => match (global.init, init) {
+        (Some(_), Some(_)) => {
+          return Err(redefinition_error(
+            &decl.name,
+            decl.position,
+            global.position,
+          ))
+        }
+        (None, Some(_)) => {
+          global.init = init;
+          global.defined = true;
+          global.position = decl.position;
+        }
+        _ => global.defined |= defined,
+      },
+      None => self.globals.push(Global {
+        name: decl.name,
+        typ: decl.typ,
+        init,
+        defined,
+        position: decl.position,
+      }),
+    }
+    Ok(())
+  }
+
+  /// Records the return type of a function, whose name can't be taken by a
+  /// file-scope variable as well.
+  fn declare_function(
+    &mut self,
+    name: &str,
+    ret_type: Type,
+    args: &ArgList,
+    position: Position,
+  ) -> Result<(), Diagnostic> {
+    if let Some(global) = self.globals.iter().find(|global| global.name == name) {
+      return Err(kind_redefinition_error(name, position, global.position));
+    }
+    self.functions.entry(String::from(name)).or_insert_with(|| {
+      let params = args.container.iter().map(|arg| arg.typ.clone()).collect();
+      (ret_type, params, position)
+    });
+    Ok(())
   }
 
   fn gen_module_prolouge(&mut self) {
     self.set(".intel_syntax noprefix");
     self.set_newline();
     self.set(".text");
-    self.set(".section .rodata");
-    self.set(".LC0:");
-    self.inc_indent();
-    self.set(".string \"%lu\\n\"");
-    self.set(".text");
-    self.dec_indent();
-    self.set_newline();
-    self.set(".globl main");
     self.set_newline();
   }
 
-  fn gen_fn(&mut self, name: &str, args: ArgList, body: Vec<Box<Stmt>>) {
+  fn gen_rodata(&mut self) {
+    if self.string_pool.is_empty() && self.jump_tables.is_empty() {
+      return;
+    }
+    self.set(".section .rodata");
+    for (i, value) in self.string_pool.clone().iter().enumerate() {
+      self.set(&format!(".LC{}:", i));
+      self.inc_indent();
+      self.set(&format!(".string \"{}\"", escape_asm_string(value)));
+      self.dec_indent();
+    }
+    // entries are offsets from the table, which needs no relocation in PIE
+    for (table, targets) in self.jump_tables.clone().iter() {
+      self.set(".balign 4");
+      self.set_label(table);
+      self.inc_indent();
+      for target in targets {
+        self.set(&format!(".long {} - {}", target, table));
+      }
+      self.dec_indent();
+    }
+  }
+
+  fn gen_data(&mut self) {
+    let (data, bss): (Vec<Global>, Vec<Global>) = self
+      .globals
+      .clone()
+      .into_iter()
+      .filter(|global| global.defined)
+      .partition(|global| global.init.unwrap_or(0) != 0);
+    if !data.is_empty() {
+      self.set(".data");
+      for global in data.iter() {
+        self.gen_global_label(&global.name, &global.typ);
+        self.inc_indent();
+        let directive = match self.layout.size_of(&global.typ) {
+          1 => ".byte",
+          2 => ".short",
+          4 => ".long",
+          _ => ".quad",
+        };
+        self.set(&format!("{} {}", directive, global.init.unwrap()));
+        self.dec_indent();
+      }
+    }
+    if !bss.is_empty() {
+      self.set(".bss");
+      for global in bss.iter() {
+        self.gen_global_label(&global.name, &global.typ);
+        self.inc_indent();
+        self.set(&format!(".zero {}", self.layout.size_of(&global.typ)));
+        self.dec_indent();
+      }
+    }
+  }
+
+  fn gen_global_label(&mut self, name: &str, typ: &Type) {
+    self.set(&format!(".globl {}", name));
+    self.set(&format!(".balign {}", self.layout.align_of(typ)));
+    self.set_label(name);
+  }
+
+  /// Returns the label of the string literal, sharing it between equal literals.
+  fn string_label(&mut self, value: &[u8]) -> String {
+    let index = match self.string_pool.iter().position(|s| s[..] == *value) {
+      Some(index) => index,
+      None => {
+        self.string_pool.push(value.to_vec());
+        self.string_pool.len() - 1
+      }
+    };
+    format!(".LC{}", index)
+  }
+
+  fn gen_fn(&mut self, name: &str, args: ArgList, body: Vec<Stmt>) -> Result<(), Diagnostic> {
     self.rsp_count = 0;
-    let mut env = Env::new();
-    self.gen_fn_prolouge(&name, &env);
+    self.fn_label_index = self.label_index;
+    self.label_index += 1;
+    let mut env = Env::new(self.layout);
+    self.gen_fn_prolouge(name, &env);
 
+    // locals are allocated while generating the body, so the frame size is
+    // patched in afterwards
+    let frame_index = self.code_list.len();
+    self.set("sub rsp, 0");
     let reg_names = self.arg_register_names();
+    if let Some(arg) = args.container.get(reg_names.len()) {
+      return Err(
+        Diagnostic::error(
+          format!("too many parameters in definition of '{}'", name),
+          arg.position,
+        )
+        .with_note("at most 6 arguments can be passed in registers"),
+      );
+    }
     for (i, arg) in args.container.iter().enumerate() {
-      let offset = env.alloc(&arg.name);
-      let reg_name = reg_names[i];
-      self.set(&format!("mov -{}[rbp], {}", offset, reg_name));
+      let offset = env.alloc(&arg.name, &arg.typ, arg.position)?;
+      // only the declared width of an argument is passed
+      self.gen_extend(&arg.typ, reg_names[i]);
+      self.gen_store(&arg.typ, &format!("-{}[rbp]", offset), reg_names[i]);
     }
-    self.set(&format!("sub rsp, {}", args.container.len() * 8));
 
-    self.gen_block(body, &mut env);
-    self.gen_fn_epilouge(&name);
+    self.gen_block(body, &mut env)?;
+    self.code_list[frame_index] = self.format_line(&format!("sub rsp, {}", env.frame_size()));
+    self.gen_fn_epilouge(name);
+    Ok(())
   }
 
   fn gen_fn_prolouge(&mut self, name: &str, _env: &Env) {
+    // functions have external linkage, so other translation units can call them
+    self.set(&format!(".globl {}", name));
     self.set(&format!("{}:", name));
     self.inc_indent();
     self.set_push("rbp");
@@ -137,79 +464,188 @@ impl Codegen {
 
   fn gen_fn_epilouge(&mut self, name: &str) {
     self.set(&format!("# function '{}' end", name));
-    self.set_pop("rax");
     self.set("mov rsp, rbp");
     self.set_pop("rbp");
     self.set("ret");
     self.dec_indent();
   }
 
-  fn gen_block(&mut self, body: Vec<Box<Stmt>>, env: &mut Env) {
+  fn gen_block(&mut self, body: Vec<Stmt>, env: &mut Env) -> Result<(), Diagnostic> {
     for stmt in body {
-      match *stmt {
-        Stmt::ExprStmt { expr } => self.gen_expr(&expr, env),
-        Stmt::IfStmt {
-          cond,
-          true_body,
-          false_body,
-        } => self.gen_if(cond, true_body, false_body, env),
-        Stmt::ForStmt {
-          cond,
-          prologue,
-          epilogue,
-          body,
-        } => self.gen_for(cond, prologue, epilogue, body, env),
-        Stmt::ReturnStmt { expr } => {
-          self.gen_return(expr, env);
+      self.gen_stmt(stmt, env)?;
+    }
+    Ok(())
+  }
+
+  fn gen_stmt(&mut self, stmt: Stmt, env: &mut Env) -> Result<(), Diagnostic> {
+    match stmt {
+      Stmt::ExprStmt { expr } => {
+        self.gen_expr(&expr, env)?;
+        self.set_pop("rax");
+      }
+      Stmt::VarDecl { decls, .. } => {
+        // each variable is in scope from its own declarator on
+        for decl in decls {
+          if decl.typ.is_array() && decl.init.is_some() {
+            return Err(array_init_error(decl.position));
+          }
+          let offset = env.alloc(&decl.name, &decl.typ, decl.position)?;
+          if let Some(init) = decl.init {
+            let message = format!(
+              "initializing '{}' with an expression of incompatible type 'void'",
+              decl.typ
+            );
+            self.check_value(&init, env, &message)?;
+            self.gen_expr(&init, env)?;
+            self.set_pop("rax");
+            self.gen_store(&decl.typ, &format!("-{}[rbp]", offset), "rax");
+          }
         }
-        Stmt::FnStmt { .. } => panic!("FnStmt is not supported in a function..."),
       }
+      Stmt::Block { body, .. } => {
+        env.enter_scope();
+        self.gen_block(body, env)?;
+        env.leave_scope();
+      }
+      Stmt::Empty { .. } => {}
+      Stmt::IfStmt {
+        cond,
+        true_body,
+        false_body,
+        ..
+      } => self.gen_if(cond, *true_body, false_body.map(|body| *body), env)?,
+      Stmt::ForStmt {
+        cond,
+        prologue,
+        epilogue,
+        body,
+        ..
+      } => self.gen_for(cond, prologue, epilogue, *body, env)?,
+      Stmt::WhileStmt { cond, body, .. } => self.gen_while(cond, *body, env)?,
+      Stmt::DoWhileStmt { body, cond, .. } => self.gen_do_while(*body, cond, env)?,
+      Stmt::SwitchStmt {
+        cond, typ, body, ..
+      } => self.gen_switch(cond, &typ, *body, env)?,
+      Stmt::CaseStmt { body, .. } | Stmt::DefaultStmt { body, .. } => {
+        let label = self
+          .case_labels
+          .last_mut()
+          .and_then(|labels| labels.pop())
+          .expect("'case' outside a switch");
+        self.set_label(&label);
+        self.gen_stmt(*body, env)?;
+      }
+      Stmt::LabelStmt { name, body, .. } => {
+        let label = self.user_label(&name);
+        self.set_label(&label);
+        self.gen_stmt(*body, env)?;
+      }
+      Stmt::GotoStmt { name, .. } => {
+        let label = self.user_label(&name);
+        self.set(&format!("jmp {}", label));
+      }
+      Stmt::BreakStmt { .. } => {
+        // the validator rejects jumps outside a loop before codegen
+        let label = self.break_labels.last().expect("'break' outside a loop");
+        self.set(&format!("jmp {}", label));
+      }
+      Stmt::ContinueStmt { .. } => {
+        let label = self
+          .continue_labels
+          .last()
+          .expect("'continue' outside a loop");
+        self.set(&format!("jmp {}", label));
+      }
+      Stmt::ReturnStmt { expr, .. } => {
+        self.gen_return(expr, env)?;
+      }
+      Stmt::FnStmt { position, .. } => {
+        return Err(Diagnostic::error(
+          "function definition is not allowed here",
+          position,
+        ))
+      }
+      Stmt::FnDecl { .. } => {}
     }
+    Ok(())
   }
 
-  fn gen_lvalue(&mut self, expr: &Box<Expr>, env: &mut Env, alloc_ok: bool) {
-    match &**expr {
-      Expr::Id { name, position: _ } => {
-        let offset = match env.get_offset(&name) {
-          Some(offset) => offset,
-          None => {
-            if alloc_ok {
-              env.alloc(&name)
-            } else {
-              panic!("Non allocated id found...")
-            }
+  fn gen_lvalue(&mut self, expr: &Expr, env: &mut Env) -> Result<(), Diagnostic> {
+    match expr {
+      Expr::Id { name, position } => {
+        // locals shadow the globals
+        if let Some(var) = env.get(name) {
+          self.set("mov rax, rbp");
+          self.set(&format!("sub rax, {}", var.offset));
+        } else if let Some(global) = self.globals.iter().find(|global| global.name == *name) {
+          if global.defined {
+            self.set(&format!("lea rax, {}[rip]", name));
+          } else {
+            // the definition may be in a shared library, so the address is
+            // taken from the GOT
+            self.set(&format!("mov rax, {}@GOTPCREL[rip]", name));
           }
-        };
-        self.set("mov rax, rbp");
-        self.set(&format!("sub rax, {}", offset));
+        } else {
+          return Err(Diagnostic::error(
+            format!("use of undeclared identifier '{}'", name),
+            *position,
+          ));
+        }
         self.set_push("rax");
+        Ok(())
       }
-      _ => panic!("Only Id can be refered as lvalue..."),
+      // the address is the value of the pointer
+      Expr::UnaryOp {
+        op: UnaryOpType::Deref,
+        rhs,
+        position,
+      } => {
+        self.deref_type(rhs, *position, env)?;
+        self.gen_expr(rhs, env)
+      }
+      _ => Err(Diagnostic::error(
+        "expression is not assignable",
+        expr.position(),
+      )),
     }
   }
 
   fn gen_if(
     &mut self,
     cond: Box<Expr>,
-    true_body: Vec<Box<Stmt>>,
-    false_body: Option<Vec<Box<Stmt>>>,
+    true_body: Stmt,
+    false_body: Option<Stmt>,
     env: &mut Env,
-  ) {
-    self.gen_expr(&&cond, env);
-    self.set_pop("rax");
-    self.set("cmp rax, 0");
+  ) -> Result<(), Diagnostic> {
+    let label_else = self.make_label("else");
+    let label_end = self.make_label("end");
+    self.label_index += 1;
+    self.gen_cond(&cond, env)?;
     if let Some(false_body) = false_body {
-      self.set(&format!("je .Lelse_{}", self.label_index));
-      self.gen_block(true_body, env);
-      self.set(&format!("jmp .Lend_{}", self.label_index));
-      self.set(&format!(".Lelse_{}:", self.label_index));
-      self.gen_block(false_body, env);
+      self.set(&format!("je {}", label_else));
+      self.gen_stmt(true_body, env)?;
+      self.set(&format!("jmp {}", label_end));
+      self.set_label(&label_else);
+      self.gen_stmt(false_body, env)?;
     } else {
-      self.set(&format!("je .Lend_{}", self.label_index));
-      self.gen_block(true_body, env);
+      self.set(&format!("je {}", label_end));
+      self.gen_stmt(true_body, env)?;
     }
-    self.set(&format!(".Lend_{}:", self.label_index));
-    self.label_index += 1;
+    self.set_label(&label_end);
+    Ok(())
+  }
+
+  /// Generates the controlling expression `cond` and compares it with 0.
+  fn gen_cond(&mut self, cond: &Expr, env: &mut Env) -> Result<(), Diagnostic> {
+    self.check_value(
+      cond,
+      env,
+      "statement requires expression of scalar type ('void' invalid)",
+    )?;
+    self.gen_expr(cond, env)?;
+    self.set_pop("rax");
+    self.set("cmp rax, 0");
+    Ok(())
   }
 
   fn gen_for(
@@ -217,94 +653,342 @@ impl Codegen {
     cond: Option<Box<Expr>>,
     prologue: Option<Box<Expr>>,
     epilogue: Option<Box<Expr>>,
-    body: Vec<Box<Stmt>>,
+    body: Stmt,
     env: &mut Env,
-  ) {
+  ) -> Result<(), Diagnostic> {
+    let label_begin = self.make_label("for_begin");
+    let label_continue = self.make_label("for_continue");
+    let label_end = self.make_label("for_end");
+    self.label_index += 1;
     if let Some(expr) = prologue {
-      self.gen_expr(&expr, env);
+      self.gen_expr(&expr, env)?;
+      self.set_pop("rax");
     }
-    let label_begin = self.set_label("for_begin");
-    let label_end = self.make_label("for_end");
+    self.set_label(&label_begin);
     if let Some(expr) = cond {
-      self.gen_expr(&expr, env);
+      self.gen_cond(&expr, env)?;
+      self.set(&format!("je {}", label_end));
     }
-    self.set_pop("rax");
-    self.set("cmp rax, 0");
-    self.set(&format!("je {}", label_end));
-    self.gen_block(body, env);
+    self.gen_loop_body(body, &label_end, &label_continue, env)?;
+    self.set_label(&label_continue);
     if let Some(expr) = epilogue {
-      self.gen_expr(&expr, env);
+      self.gen_expr(&expr, env)?;
+      self.set_pop("rax");
     }
     self.set(&format!("jmp {}", label_begin));
-    let _ = self.set_label("for_end");
+    self.set_label(&label_end);
+    Ok(())
+  }
+
+  fn gen_while(&mut self, cond: Box<Expr>, body: Stmt, env: &mut Env) -> Result<(), Diagnostic> {
+    let label_begin = self.make_label("while_begin");
+    let label_end = self.make_label("while_end");
     self.label_index += 1;
+    self.set_label(&label_begin);
+    self.gen_cond(&cond, env)?;
+    self.set(&format!("je {}", label_end));
+    self.gen_loop_body(body, &label_end, &label_begin, env)?;
+    self.set(&format!("jmp {}", label_begin));
+    self.set_label(&label_end);
+    Ok(())
   }
 
-  fn gen_return(&mut self, lhs: Option<Box<Expr>>, env: &mut Env) {
-    if let Some(lhs) = lhs {
-      self.gen_expr(&lhs, env);
+  fn gen_do_while(&mut self, body: Stmt, cond: Box<Expr>, env: &mut Env) -> Result<(), Diagnostic> {
+    let label_begin = self.make_label("do_begin");
+    let label_continue = self.make_label("do_continue");
+    let label_end = self.make_label("do_end");
+    self.label_index += 1;
+    self.set_label(&label_begin);
+    self.gen_loop_body(body, &label_end, &label_continue, env)?;
+    self.set_label(&label_continue);
+    self.gen_cond(&cond, env)?;
+    self.set(&format!("jne {}", label_begin));
+    self.set_label(&label_end);
+    Ok(())
+  }
+
+  fn gen_switch(
+    &mut self,
+    cond: Box<Expr>,
+    typ: &Type,
+    body: Stmt,
+    env: &mut Env,
+  ) -> Result<(), Diagnostic> {
+    let label_table = self.make_label("jump_table");
+    let label_case = self.make_label("case");
+    let label_default = self.make_label("default");
+    let label_end = self.make_label("switch_end");
+    self.label_index += 1;
+
+    let mut cases = Vec::new();
+    collect_cases(&body, &mut cases);
+    let mut labels = Vec::new();
+    let mut values = Vec::new();
+    let mut label_otherwise = label_end.clone();
+    for (i, case) in cases.into_iter().enumerate() {
+      match case {
+        Some(value) => {
+          let label = format!("{}_{}", label_case, i);
+          values.push((self.layout.convert(value, typ), label.clone()));
+          labels.push(label);
+        }
+        None => {
+          label_otherwise = label_default.clone();
+          labels.push(label_default.clone());
+        }
+      }
     }
+
+    self.check_value(
+      &cond,
+      env,
+      "statement requires expression of scalar type ('void' invalid)",
+    )?;
+    self.gen_expr(&cond, env)?;
     self.set_pop("rax");
+    match jump_table_range(&values) {
+      Some((min, max)) => {
+        let targets = (min..=max)
+          .map(|value| match values.iter().find(|(v, _)| *v == value) {
+            Some((_, label)) => label.clone(),
+            None => label_otherwise.clone(),
+          })
+          .collect();
+        self.jump_tables.push((label_table.clone(), targets));
+        // values below 'min' wrap around and fail the unsigned check too
+        self.set(&format!("mov rdi, {}", min));
+        self.set("sub rax, rdi");
+        self.set(&format!("cmp rax, {}", max - min));
+        self.set(&format!("ja {}", label_otherwise));
+        self.set(&format!("lea rdi, {}[rip]", label_table));
+        self.set("movsxd rax, DWORD PTR [rdi + rax * 4]");
+        self.set("add rax, rdi");
+        self.set("jmp rax");
+      }
+      None => {
+        for (value, label) in values.iter() {
+          // 'cmp' only takes a sign-extended 32-bit immediate
+          if (i32::MIN as i64..=i32::MAX as i64).contains(value) {
+            self.set(&format!("cmp rax, {}", value));
+          } else {
+            self.set(&format!("mov rdi, {}", value));
+            self.set("cmp rax, rdi");
+          }
+          self.set(&format!("je {}", label));
+        }
+        self.set(&format!("jmp {}", label_otherwise));
+      }
+    }
+
+    labels.reverse();
+    self.case_labels.push(labels);
+    self.break_labels.push(label_end.clone());
+    let result = self.gen_stmt(body, env);
+    self.break_labels.pop();
+    self.case_labels.pop();
+    result?;
+    self.set_label(&label_end);
+    Ok(())
+  }
+
+  fn gen_loop_body(
+    &mut self,
+    body: Stmt,
+    label_break: &str,
+    label_continue: &str,
+    env: &mut Env,
+  ) -> Result<(), Diagnostic> {
+    self.break_labels.push(String::from(label_break));
+    self.continue_labels.push(String::from(label_continue));
+    let result = self.gen_stmt(body, env);
+    self.break_labels.pop();
+    self.continue_labels.pop();
+    result
+  }
+
+  fn gen_return(&mut self, lhs: Option<Box<Expr>>, env: &mut Env) -> Result<(), Diagnostic> {
+    if let Some(lhs) = lhs {
+      if self.ret_type != Type::Void {
+        let message = format!(
+          "returning 'void' from a function with incompatible result type '{}'",
+          self.ret_type
+        );
+        self.check_value(&lhs, env, &message)?;
+      }
+      self.gen_expr(&lhs, env)?;
+      self.set_pop("rax");
+      let ret_type = self.ret_type.clone();
+      self.gen_convert(&ret_type, "rax");
+    }
     self.set("mov rsp, rbp");
     self.set_pop("rbp");
     self.set("ret");
+    Ok(())
   }
 
-  fn gen_expr(&mut self, expr: &Box<Expr>, env: &mut Env) {
-    match &**expr {
-      Expr::Id {
-        name: _,
-        position: _,
-      } => {
-        self.gen_lvalue(expr, env, false);
+  fn gen_expr(&mut self, expr: &Expr, env: &mut Env) -> Result<(), Diagnostic> {
+    match expr {
+      Expr::Id { .. } => {
+        let typ = self.expr_type(expr, env);
+        self.gen_lvalue(expr, env)?;
         self.set_pop("rax");
-        self.set("mov rax, [rax]");
+        self.gen_load(&typ);
         self.set_push("rax");
       }
-      Expr::Number { value, position: _ } => {
+      Expr::Number { value, .. } => {
+        // 'push' only takes a sign-extended 32-bit immediate
+        if *value <= i32::MAX as u64 {
+          self.set_push(&value.to_string());
+        } else {
+          self.set(&format!("mov rax, {}", value));
+          self.set_push("rax");
+        }
+      }
+      Expr::Char { value, .. } => {
+        // 'char' is signed on x86-64
+        self.set_push(&(*value as i8).to_string());
+      }
+      Expr::TypeOp { op, typ, .. } => {
+        let value = match op {
+          TypeOpType::Sizeof => self.layout.size_of(typ),
+          TypeOpType::Alignof => self.layout.align_of(typ),
+        };
         self.set_push(&value.to_string());
       }
+      Expr::Str { value, .. } => {
+        let label = self.string_label(value);
+        self.set(&format!("lea rax, {}[rip]", label));
+        self.set_push("rax");
+      }
       Expr::Call {
         name,
         args,
-        position: _,
+        position,
       } => {
         if args.len() > 6 {
-          panic!("Currently call args must be less than 7...");
+          return Err(
+            Diagnostic::error(
+              format!("too many arguments in call to '{}'", name),
+              *position,
+            )
+            .with_note("at most 6 arguments can be passed in registers"),
+          );
         }
         for arg in args.iter() {
-          self.gen_expr(&arg, env);
+          self.check_value(arg, env, "argument type 'void' is incomplete")?;
+          self.gen_expr(arg, env)?;
         }
         // we must pop in reverse order, so indexing is complex
         let reg_names = self.arg_register_names();
         let num_args = args.len();
+        // arguments are converted to the types of the parameters declared
+        let params = match self.functions.get(name) {
+          Some((_, params, _)) => params.clone(),
+          None => Vec::new(),
+        };
         for i in 1..=num_args {
           let reg_name = reg_names[num_args - i];
           self.set_pop("rax");
           self.set(&format!("mov {}, rax", reg_name));
+          if let Some(typ) = params.get(num_args - i) {
+            self.gen_convert(typ, reg_name);
+          }
         }
         // push dummy for aligning RSP by 16 bytes
         if self.rsp_count % 2 == 0 {
           self.set("sub rsp, 8");
         }
+        // al holds the number of vector registers used by a variadic call
+        self.set("mov rax, 0");
         self.set(&format!("call {}", name));
         // pop dummy for aligning RSP by 16 bytes
         if self.rsp_count % 2 == 0 {
           self.set("add rsp, 8");
         }
+        // only the bytes of the return type are defined in 'rax'
+        let ret_type = self.expr_type(expr, env);
+        self.gen_extend(&ret_type, "rax");
         self.set_push("rax");
       }
-      Expr::UnaryOp {
-        op,
-        rhs,
-        position: _,
-      } => {
-        self.gen_expr(&rhs, env);
-        self.set_pop("rdi");
-        self.set("mov rax, 0");
+      Expr::UnaryOp { op, rhs, position } => {
         match op {
-          UnaryOpType::Minus => self.set("sub rax, rdi"),
-          UnaryOpType::Plus => self.set("add rax, rdi"),
+          UnaryOpType::PreInc
+          | UnaryOpType::PreDec
+          | UnaryOpType::PostInc
+          | UnaryOpType::PostDec => {
+            let typ = self.assigned_type(rhs, env)?;
+            // a pointer steps over a whole pointee
+            let layout = self.layout;
+            let step = typ.pointee().map_or(1, |pointee| layout.size_of(pointee));
+            self.gen_lvalue(rhs, env)?;
+            self.set_pop("rsi");
+            self.set("mov rax, rsi");
+            self.gen_load(&typ);
+            self.set("mov rdi, rax");
+            match op {
+              UnaryOpType::PreInc | UnaryOpType::PostInc => self.set(&format!("add rax, {}", step)),
+              _ => self.set(&format!("sub rax, {}", step)),
+            };
+            self.gen_store(&typ, "[rsi]", "rax");
+            // the prefix forms yield the new value as stored, the postfix
+            // ones the old
+            match op {
+              UnaryOpType::PreInc | UnaryOpType::PreDec => {
+                self.set("mov rax, rsi");
+                self.gen_load(&typ);
+              }
+              _ => self.set("mov rax, rdi"),
+            };
+          }
+          UnaryOpType::AddrOf => {
+            if !matches!(
+              **rhs,
+              Expr::Id { .. }
+                | Expr::UnaryOp {
+                  op: UnaryOpType::Deref,
+                  ..
+                }
+            ) {
+              return Err(Diagnostic::error(
+                format!(
+                  "cannot take the address of an rvalue of type '{}'",
+                  self.expr_type(rhs, env)
+                ),
+                *position,
+              ));
+            }
+            self.gen_lvalue(rhs, env)?;
+            self.set_pop("rax");
+          }
+          UnaryOpType::Deref => {
+            let typ = self.deref_type(rhs, *position, env)?;
+            self.gen_expr(rhs, env)?;
+            self.set_pop("rax");
+            self.gen_load(&typ);
+          }
+          _ => {
+            self.check_value(rhs, env, "invalid argument type 'void' to unary expression")?;
+            let typ = self.expr_type(rhs, env).promoted();
+            self.gen_expr(rhs, env)?;
+            self.set_pop("rax");
+            match op {
+              UnaryOpType::Plus => {}
+              UnaryOpType::Minus => {
+                self.set("neg rax");
+                self.gen_extend(&typ, "rax");
+              }
+              UnaryOpType::BitNot => {
+                self.set("not rax");
+                self.gen_extend(&typ, "rax");
+              }
+              UnaryOpType::Not => {
+                self.set("cmp rax, 0");
+                self.set("sete al");
+                self.set("movzb rax, al");
+              }
+              _ => unreachable!(),
+            }
+          }
         }
         self.set_push("rax");
       }
@@ -312,83 +996,317 @@ impl Codegen {
         op,
         lhs,
         rhs,
-        position: _,
+        position,
       } => {
         match op {
           BinaryOpType::Assign => {
-            self.gen_lvalue(&lhs, env, true);
-            self.gen_expr(&rhs, env);
+            // the value is read back, as storing converts it to the lhs type
+            let typ = self.assigned_type(lhs, env)?;
+            let message = format!("assigning to '{}' from incompatible type 'void'", typ);
+            self.check_value(rhs, env, &message)?;
+            self.gen_lvalue(lhs, env)?;
+            self.gen_expr(rhs, env)?;
             self.set_pop("rdi");
             self.set_pop("rax");
-            self.set("mov [rax], rdi");
-            self.set_push("rdi");
+            self.gen_store(&typ, "[rax]", "rdi");
+            self.gen_load(&typ);
           }
-          BinaryOpType::Inc | BinaryOpType::Dec => {
-            self.gen_lvalue(&lhs, env, false);
-            self.gen_expr(&rhs, env);
-            self.set_pop("rdi");
+          BinaryOpType::Comma => {
+            self.gen_expr(lhs, env)?;
             self.set_pop("rax");
-            self.set("mov rcx, [rax]");
-            match op {
-              BinaryOpType::Inc => self.set("add rcx, rdi"),
-              BinaryOpType::Dec => self.set("sub rcx, rdi"),
-              _ => panic!("Unreachable"),
+            self.gen_expr(rhs, env)?;
+            self.set_pop("rax");
+          }
+          BinaryOpType::LogicalAnd | BinaryOpType::LogicalOr => {
+            // the rhs is skipped once the lhs decides the result; the flags
+            // at the end label are those of the last operand compared
+            let (name, jump) = match op {
+              BinaryOpType::LogicalAnd => ("and_end", "je"),
+              _ => ("or_end", "jne"),
             };
-            self.set("mov [rax], rcx");
-            self.set_push("rdi");
+            check_operands(
+              op,
+              &self.expr_type(lhs, env),
+              &self.expr_type(rhs, env),
+              *position,
+            )?;
+            let label_end = self.make_label(name);
+            self.label_index += 1;
+            self.gen_expr(lhs, env)?;
+            self.set_pop("rax");
+            self.set("cmp rax, 0");
+            self.set(&format!("{} {}", jump, label_end));
+            self.gen_expr(rhs, env)?;
+            self.set_pop("rax");
+            self.set("cmp rax, 0");
+            self.set_label(&label_end);
+            self.set("setne al");
+            self.set("movzb rax, al");
           }
           _ => {
-            self.gen_expr(&lhs, env);
-            self.gen_expr(&rhs, env);
-            self.set_pop("rdi");
-            self.set_pop("rax");
-            match op {
-              BinaryOpType::Add => self.set("add rax, rdi"),
-              BinaryOpType::Sub => self.set("sub rax, rdi"),
-              BinaryOpType::Mul => self.set("imul rax, rdi"),
-              BinaryOpType::Div => {
-                self.set("cqo");
-                self.set("idiv rdi");
+            let compound_op = op.compound_op();
+            let lhs_type = match compound_op {
+              Some(_) => self.assigned_type(lhs, env)?,
+              None => self.expr_type(lhs, env).decayed(),
+            };
+            let rhs_type = self.expr_type(rhs, env).decayed();
+            check_operands(
+              compound_op.as_ref().unwrap_or(op),
+              &lhs_type,
+              &rhs_type,
+              *position,
+            )?;
+            match compound_op {
+              Some(op) => {
+                check_compound_result(&lhs_type, &op.result_type(&lhs_type, &rhs_type), *position)?;
+                // the address is computed once and kept in 'rsi', which the
+                // operator instructions leave alone
+                self.gen_lvalue(lhs, env)?;
+                self.gen_expr(rhs, env)?;
+                self.set_pop("rdi");
+                self.set_pop("rsi");
+                self.set("mov rax, rsi");
+                self.gen_load(&lhs_type);
+                self.gen_binary_inst(&op, &lhs_type, &rhs_type);
+                self.gen_store(&lhs_type, "[rsi]", "rax");
+                self.set("mov rax, rsi");
+                self.gen_load(&lhs_type);
               }
-              _ => {
-                self.set("cmp rax, rdi");
-                match op {
-                  BinaryOpType::Eq => {
-                    self.set("sete al");
-                  }
-                  BinaryOpType::Ne => {
-                    self.set("setne al");
-                  }
-                  BinaryOpType::Lt => {
-                    self.set("setl al");
-                  }
-                  BinaryOpType::Le => {
-                    self.set("setle al");
-                  }
-                  BinaryOpType::Gt => {
-                    self.set("setg al");
-                  }
-                  BinaryOpType::Ge => {
-                    self.set("setge al");
-                  }
-                  _ => panic!("Unreachable"),
-                };
-                self.set("movzb rax, al");
+              None => {
+                self.gen_expr(lhs, env)?;
+                self.gen_expr(rhs, env)?;
+                self.set_pop("rdi");
+                self.set_pop("rax");
+                self.gen_binary_inst(op, &lhs_type, &rhs_type);
               }
-            };
+            }
           }
         };
         self.set_push("rax");
       }
+      Expr::Conditional {
+        cond,
+        then_expr,
+        else_expr,
+        ..
+      } => {
+        // only the selected operand is evaluated
+        let label_else = self.make_label("cond_else");
+        let label_end = self.make_label("cond_end");
+        self.label_index += 1;
+        self.check_value(
+          cond,
+          env,
+          "used type 'void' where arithmetic or pointer type is required",
+        )?;
+        self.gen_expr(cond, env)?;
+        self.set_pop("rax");
+        self.set("cmp rax, 0");
+        self.set(&format!("je {}", label_else));
+        self.gen_expr(then_expr, env)?;
+        self.set_pop("rax");
+        self.set(&format!("jmp {}", label_end));
+        self.set_label(&label_else);
+        self.gen_expr(else_expr, env)?;
+        self.set_pop("rax");
+        self.set_label(&label_end);
+        self.set_push("rax");
+      }
     }
+    Ok(())
   }
 
-  fn set(&mut self, cmd: &str) {
-    let mut indent = String::new();
-    for _ in 0..self.indent {
-      indent += " ";
+  /// Replaces the address in 'rax' with the value of type `typ` stored there.
+  fn gen_load(&mut self, typ: &Type) {
+    // the value of an array is the address of its first element, which is
+    // the address itself
+    if typ.is_array() {
+      return;
+    }
+    match self.layout.size_of(typ) {
+      1 if typ.is_unsigned() => self.set("movzx eax, BYTE PTR [rax]"),
+      1 => self.set("movsx rax, BYTE PTR [rax]"),
+      2 if typ.is_unsigned() => self.set("movzx eax, WORD PTR [rax]"),
+      2 => self.set("movsx rax, WORD PTR [rax]"),
+      4 if typ.is_unsigned() => self.set("mov eax, DWORD PTR [rax]"),
+      4 => self.set("movsxd rax, DWORD PTR [rax]"),
+      _ => self.set("mov rax, [rax]"),
+    }
+  }
+
+  /// Stores the value of type `typ` in the register `reg` to `dest`.
+  fn gen_store(&mut self, typ: &Type, dest: &str, reg: &str) {
+    let sub_reg = sub_register(reg, self.layout.size_of(typ));
+    // any nonzero value becomes 1 in a '_Bool'
+    if *typ == Type::Bool {
+      self.set(&format!("cmp {}, 0", reg));
+      self.set(&format!("setne {}", sub_reg));
     }
-    self.code_list.push(format!("{}{}\n", indent, cmd));
+    self.set(&format!("mov {}, {}", dest, sub_reg));
+  }
+
+  /// Converts the 64-bit value in `reg` to `typ`, keeping it extended to 64
+  /// bits.
+  fn gen_convert(&mut self, typ: &Type, reg: &str) {
+    if *typ == Type::Bool {
+      self.set(&format!("cmp {}, 0", reg));
+      self.set(&format!("setne {}", sub_register(reg, 1)));
+    }
+    self.gen_extend(typ, reg);
+  }
+
+  /// Extends the value of type `typ` in the low bytes of `reg` to 64 bits.
+  fn gen_extend(&mut self, typ: &Type, reg: &str) {
+    let size = self.layout.size_of(typ);
+    let sub_reg = sub_register(reg, size);
+    match size {
+      1 | 2 if typ.is_unsigned() => {
+        self.set(&format!("movzx {}, {}", sub_register(reg, 4), sub_reg))
+      }
+      1 | 2 => self.set(&format!("movsx {}, {}", reg, sub_reg)),
+      4 if typ.is_unsigned() => self.set(&format!("mov {}, {}", sub_reg, sub_reg)),
+      4 => self.set(&format!("movsxd {}, {}", reg, sub_reg)),
+      _ => {}
+    }
+  }
+
+  /// Returns the type `expr` points to, which is an error unless it's a
+  /// pointer.
+  fn deref_type(&self, expr: &Expr, position: Position, env: &Env) -> Result<Type, Diagnostic> {
+    let typ = self.expr_type(expr, env).decayed();
+    match typ.pointee() {
+      Some(pointee) => Ok(pointee.clone()),
+      None => Err(Diagnostic::error(
+        format!("indirection requires pointer operand ('{}' invalid)", typ),
+        position,
+      )),
+    }
+  }
+
+  /// Returns the type of the lvalue `expr` assigned to, which can't be an
+  /// array.
+  fn assigned_type(&self, expr: &Expr, env: &Env) -> Result<Type, Diagnostic> {
+    let typ = self.expr_type(expr, env);
+    if typ.is_array() {
+      return Err(Diagnostic::error(
+        format!("array type '{}' is not assignable", typ),
+        expr.position(),
+      ));
+    }
+    if typ == Type::Void {
+      return Err(Diagnostic::error(
+        "incomplete type 'void' is not assignable",
+        expr.position(),
+      ));
+    }
+    Ok(typ)
+  }
+
+  /// Fails with `message` at `expr` if `expr` is 'void', e.g. a call to a
+  /// 'void' function, as its value is used.
+  fn check_value(&self, expr: &Expr, env: &Env, message: &str) -> Result<(), Diagnostic> {
+    if self.expr_type(expr, env) == Type::Void {
+      return Err(Diagnostic::error(message, expr.position()));
+    }
+    Ok(())
+  }
+
+  /// Returns the type of `expr` without generating code for it. Undeclared
+  /// names are taken as 'int', and reported when their code is generated.
+  fn expr_type(&self, expr: &Expr, env: &Env) -> Type {
+    expr.typ(&Scope { codegen: self, env }).unwrap_or(Type::Int)
+  }
+
+  /// Emits `rax = rax op rdi` for an arithmetic or comparison operator. The
+  /// operands are converted to their common type, whose signedness picks the
+  /// instruction.
+  fn gen_binary_inst(&mut self, op: &BinaryOpType, lhs_type: &Type, rhs_type: &Type) {
+    // pointer arithmetic counts in pointees
+    match (op, lhs_type.pointee(), rhs_type.pointee()) {
+      (BinaryOpType::Add | BinaryOpType::Sub, Some(pointee), None) => {
+        self.set(&format!("imul rdi, {}", self.layout.size_of(pointee)))
+      }
+      (BinaryOpType::Add, None, Some(pointee)) => {
+        self.set(&format!("imul rax, {}", self.layout.size_of(pointee)))
+      }
+      (BinaryOpType::Sub, Some(pointee), Some(_)) => {
+        self.set("sub rax, rdi");
+        self.set(&format!("mov rdi, {}", self.layout.size_of(pointee)));
+        self.set("cqo");
+        self.set("idiv rdi");
+        return;
+      }
+      _ => {}
+    }
+    // values are kept extended to 64 bits by their own types, so 32-bit
+    // operands are converted to the type of the operation first, and the
+    // result is truncated back to it
+    let typ = match op {
+      BinaryOpType::Shl | BinaryOpType::Shr => lhs_type.promoted(),
+      _ => lhs_type.common(rhs_type),
+    };
+    let unsigned = typ.is_unsigned();
+    self.gen_extend(&typ, "rax");
+    if !matches!(op, BinaryOpType::Shl | BinaryOpType::Shr) {
+      self.gen_extend(&typ, "rdi");
+    }
+    match op {
+      BinaryOpType::Add => self.set("add rax, rdi"),
+      BinaryOpType::Sub => self.set("sub rax, rdi"),
+      BinaryOpType::Mul => self.set("imul rax, rdi"),
+      BinaryOpType::Div | BinaryOpType::Mod => {
+        if unsigned {
+          self.set("mov rdx, 0");
+          self.set("div rdi");
+        } else {
+          self.set("cqo");
+          self.set("idiv rdi");
+        }
+        if let BinaryOpType::Mod = op {
+          self.set("mov rax, rdx");
+        }
+      }
+      BinaryOpType::BitAnd => self.set("and rax, rdi"),
+      BinaryOpType::BitOr => self.set("or rax, rdi"),
+      BinaryOpType::BitXor => self.set("xor rax, rdi"),
+      BinaryOpType::Shl | BinaryOpType::Shr => {
+        // the count must be in 'cl', and only the lhs type matters
+        self.set("mov rcx, rdi");
+        let inst = match op {
+          BinaryOpType::Shl => "shl",
+          _ if unsigned => "shr",
+          _ => "sar",
+        };
+        self.set(&format!("{} rax, cl", inst));
+      }
+      _ => {
+        self.set("cmp rax, rdi");
+        let (signed_inst, unsigned_inst) = match op {
+          BinaryOpType::Eq => ("sete", "sete"),
+          BinaryOpType::Ne => ("setne", "setne"),
+          BinaryOpType::Lt => ("setl", "setb"),
+          BinaryOpType::Le => ("setle", "setbe"),
+          BinaryOpType::Gt => ("setg", "seta"),
+          BinaryOpType::Ge => ("setge", "setae"),
+          _ => unreachable!(),
+        };
+        let inst = if unsigned { unsigned_inst } else { signed_inst };
+        self.set(&format!("{} al", inst));
+        self.set("movzb rax, al");
+        // an 'int' 0 or 1 needs no truncation
+        return;
+      }
+    }
+    self.gen_extend(&typ, "rax");
+  }
+
+  fn set(&mut self, cmd: &str) {
+    let line = self.format_line(cmd);
+    self.code_list.push(line);
+  }
+
+  fn format_line(&self, cmd: &str) -> String {
+    format!("{}{}\n", " ".repeat(self.indent), cmd)
   }
 
   fn set_push(&mut self, reg_name: &str) {
@@ -401,16 +1319,21 @@ impl Codegen {
     self.rsp_count -= 1;
   }
 
-  fn set_label(&mut self, name: &str) -> String {
-    let label = self.make_label(name);
+  fn set_label(&mut self, label: &str) {
     self.code_list.push(format!("{}:\n", label));
-    label
   }
 
-  fn make_label(&mut self, name: &str) -> String {
+  /// Makes a label unique to the statement being generated; callers bump
+  /// `label_index` before generating nested statements.
+  fn make_label(&self, name: &str) -> String {
     format!(".L{}_{}", name, self.label_index)
   }
 
+  /// Makes the label of the C label `name` in the current function.
+  fn user_label(&self, name: &str) -> String {
+    format!(".Llabel_{}_{}", self.fn_label_index, name)
+  }
+
   fn set_newline(&mut self) {
     self.set("");
   }
@@ -437,3 +1360,174 @@ impl Codegen {
   // writer.flush()?;
   // }
 }
+
+/// Checks that `lhs` and `rhs` are valid operand types of `op`, which only
+/// pointers can break.
+fn check_operands(
+  op: &BinaryOpType,
+  lhs: &Type,
+  rhs: &Type,
+  position: Position,
+) -> Result<(), Diagnostic> {
+  let valid = match op {
+    BinaryOpType::Comma => true,
+    // a 'void' has no value to operate on
+    _ if *lhs == Type::Void || *rhs == Type::Void => false,
+    BinaryOpType::Add => !(lhs.is_pointer() && rhs.is_pointer()),
+    // a pointer can only be subtracted from one of the same type
+    BinaryOpType::Sub => !rhs.is_pointer() || lhs == rhs,
+    BinaryOpType::Eq
+    | BinaryOpType::Ne
+    | BinaryOpType::Lt
+    | BinaryOpType::Le
+    | BinaryOpType::Gt
+    | BinaryOpType::Ge
+    | BinaryOpType::LogicalAnd
+    | BinaryOpType::LogicalOr => true,
+    _ => !lhs.is_pointer() && !rhs.is_pointer(),
+  };
+  if valid {
+    Ok(())
+  } else {
+    Err(Diagnostic::error(
+      format!(
+        "invalid operands to binary expression ('{}' and '{}')",
+        lhs, rhs
+      ),
+      position,
+    ))
+  }
+}
+
+/// Checks that the `result` of a compound assignment can be stored back to its
+/// `lhs`, which integers and pointers can't be to each other, e.g. the
+/// 'ptrdiff_t' of 'p -= q'.
+fn check_compound_result(lhs: &Type, result: &Type, position: Position) -> Result<(), Diagnostic> {
+  let message = match (lhs.is_pointer(), result.is_pointer()) {
+    (true, false) => "incompatible integer to pointer conversion",
+    // any pointer converts to a '_Bool'
+    (false, true) if *lhs != Type::Bool => "incompatible pointer to integer conversion",
+    _ => return Ok(()),
+  };
+  Err(Diagnostic::error(
+    format!("{} assigning to '{}' from '{}'", message, lhs, result),
+    position,
+  ))
+}
+
+/// Returns the name of the low `size` bytes of the 64-bit register `reg`.
+fn sub_register(reg: &str, size: usize) -> &str {
+  let names = match reg {
+    "rax" => ["eax", "ax", "al"],
+    "rcx" => ["ecx", "cx", "cl"],
+    "rdx" => ["edx", "dx", "dl"],
+    "rsi" => ["esi", "si", "sil"],
+    "rdi" => ["edi", "di", "dil"],
+    "r8" => ["r8d", "r8w", "r8b"],
+    "r9" => ["r9d", "r9w", "r9b"],
+    _ => panic!("unknown register '{}'", reg),
+  };
+  match size {
+    1 => names[2],
+    2 => names[1],
+    4 => names[0],
+    _ => reg,
+  }
+}
+
+fn array_init_error(position: Position) -> Diagnostic {
+  Diagnostic::error("array initializer must be an initializer list", position)
+}
+
+fn void_variable_error(position: Position) -> Diagnostic {
+  Diagnostic::error("variable has incomplete type 'void'", position)
+}
+
+fn kind_redefinition_error(name: &str, position: Position, previous: Position) -> Diagnostic {
+  Diagnostic::error(
+    format!("redefinition of '{}' as different kind of symbol", name),
+    position,
+  )
+  .with_label(previous, "previous definition is here")
+}
+
+fn redefinition_error(name: &str, position: Position, previous: Position) -> Diagnostic {
+  Diagnostic::error(format!("redefinition of '{}'", name), position)
+    .with_label(previous, "previous definition is here")
+}
+
+/// Collects the values of the 'case' labels in a switch body in the order
+/// codegen meets them, with `None` for 'default'. Nested switches own their
+/// labels, so they are skipped.
+fn collect_cases(stmt: &Stmt, cases: &mut Vec<Option<i64>>) {
+  match stmt {
+    Stmt::LabelStmt { body, .. } => collect_cases(body, cases),
+    Stmt::CaseStmt { value, body, .. } => {
+      // the validator rejects non-constant labels before codegen
+      cases.push(Some(value.const_value().expect("non-constant 'case'")));
+      collect_cases(body, cases);
+    }
+    Stmt::DefaultStmt { body, .. } => {
+      cases.push(None);
+      collect_cases(body, cases);
+    }
+    Stmt::Block { body, .. } => {
+      for stmt in body {
+        collect_cases(stmt, cases);
+      }
+    }
+    Stmt::IfStmt {
+      true_body,
+      false_body,
+      ..
+    } => {
+      collect_cases(true_body, cases);
+      if let Some(body) = false_body {
+        collect_cases(body, cases);
+      }
+    }
+    Stmt::ForStmt { body, .. } | Stmt::WhileStmt { body, .. } | Stmt::DoWhileStmt { body, .. } => {
+      collect_cases(body, cases)
+    }
+    Stmt::ExprStmt { .. }
+    | Stmt::VarDecl { .. }
+    | Stmt::Empty { .. }
+    | Stmt::SwitchStmt { .. }
+    | Stmt::GotoStmt { .. }
+    | Stmt::BreakStmt { .. }
+    | Stmt::ContinueStmt { .. }
+    | Stmt::ReturnStmt { .. }
+    | Stmt::FnStmt { .. }
+    | Stmt::FnDecl { .. } => {}
+  }
+}
+
+/// Returns the value range of the cases if they are dense enough for a jump
+/// table.
+fn jump_table_range(values: &[(i64, String)]) -> Option<(i64, i64)> {
+  if values.len() < JUMP_TABLE_MIN_CASES {
+    return None;
+  }
+  let min = values.iter().map(|(v, _)| *v).min()?;
+  let max = values.iter().map(|(v, _)| *v).max()?;
+  let range = (max as i128 - min as i128 + 1) as u128;
+  if range <= (values.len() * JUMP_TABLE_MAX_RANGE_RATIO) as u128 {
+    Some((min, max))
+  } else {
+    None
+  }
+}
+
+/// Escapes bytes for the '.string' directive, which adds the trailing NUL.
+fn escape_asm_string(bytes: &[u8]) -> String {
+  let mut s = String::new();
+  for b in bytes {
+    match b {
+      b'"' => s.push_str("\\\""),
+      b'\\' => s.push_str("\\\\"),
+      0x20..=0x7e => s.push(*b as char),
+      _ => s.push_str(&format!("\\{:03o}", b)),
+    }
+  }
+  s
+}
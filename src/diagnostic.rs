@@ -0,0 +1,93 @@
+use crate::token::Position;
+use crate::utils::ToSimpleString;
+use std::fmt;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Severity {
+  Error,
+  Note,
+}
+
+impl ToSimpleString for Severity {
+  fn to_simple_string(&self) -> String {
+    match self {
+      Severity::Error => String::from("error"),
+      Severity::Note => String::from("note"),
+    }
+  }
+}
+
+impl fmt::Display for Severity {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    write!(f, "{}", self.to_simple_string())
+  }
+}
+
+/// A secondary span attached to a diagnostic, e.g. "previous definition is here".
+#[derive(Debug, Clone)]
+pub struct Label {
+  pub position: Position,
+  pub message: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct Diagnostic {
+  pub severity: Severity,
+  pub message: String,
+  pub position: Position,
+  pub labels: Vec<Label>,
+  pub notes: Vec<String>,
+}
+
+impl Diagnostic {
+  pub fn new<S: Into<String>>(severity: Severity, message: S, position: Position) -> Self {
+    Diagnostic {
+      severity,
+      message: message.into(),
+      position,
+      labels: Vec::new(),
+      notes: Vec::new(),
+    }
+  }
+
+  pub fn error<S: Into<String>>(message: S, position: Position) -> Self {
+    Self::new(Severity::Error, message, position)
+  }
+
+  pub fn with_label<S: Into<String>>(mut self, position: Position, message: S) -> Self {
+    self.labels.push(Label {
+      position,
+      message: message.into(),
+    });
+    self
+  }
+
+  pub fn with_note<S: Into<String>>(mut self, note: S) -> Self {
+    self.notes.push(note.into());
+    self
+  }
+}
+
+impl ToSimpleString for Diagnostic {
+  fn to_simple_string(&self) -> String {
+    let mut s = format!("{}{}: {}", self.severity, self.position, self.message);
+    for label in &self.labels {
+      s.push_str(&format!(
+        "\n  {}{}: {}",
+        Severity::Note,
+        label.position,
+        label.message
+      ));
+    }
+    for note in &self.notes {
+      s.push_str(&format!("\n  {}: {}", Severity::Note, note));
+    }
+    s
+  }
+}
+
+impl fmt::Display for Diagnostic {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    write!(f, "{}", self.to_simple_string())
+  }
+}
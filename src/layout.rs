@@ -0,0 +1,121 @@
+use crate::ast::Type;
+
+/// The size and the alignment of a type in bytes.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Layout {
+  pub size: usize,
+  pub align: usize,
+}
+
+impl Layout {
+  const fn new(size: usize, align: usize) -> Self {
+    Layout { size, align }
+  }
+}
+
+/// How values of each scalar type are laid out in memory on a target.
+/// Aggregates are laid out from their members, e.g. an array is its elements
+/// placed back to back.
+pub struct DataLayout {
+  pub void: Layout,
+  pub bool: Layout,
+  pub char: Layout,
+  pub short: Layout,
+  pub int: Layout,
+  pub long: Layout,
+  pub long_long: Layout,
+  pub pointer: Layout,
+}
+
+/// The x86-64 System V ABI, which is LP64: 'long' and pointers are 8 bytes.
+pub const X86_64_SYSV: DataLayout = DataLayout {
+  // 'void' has no values, but is taken as 1 byte as in GNU C so that 'void *'
+  // arithmetic counts in bytes
+  void: Layout::new(1, 1),
+  bool: Layout::new(1, 1),
+  char: Layout::new(1, 1),
+  short: Layout::new(2, 2),
+  int: Layout::new(4, 4),
+  long: Layout::new(8, 8),
+  long_long: Layout::new(8, 8),
+  pointer: Layout::new(8, 8),
+};
+
+impl DataLayout {
+  /// Returns the layout of `typ`, whose size the parser has checked.
+  pub fn layout(&self, typ: &Type) -> Layout {
+    self
+      .checked_layout(typ)
+      .expect("arrays too large are rejected when parsed")
+  }
+
+  /// Returns the layout of `typ`, or `None` if its size doesn't fit in a
+  /// 'ptrdiff_t', which offsets into it are.
+  pub fn checked_layout(&self, typ: &Type) -> Option<Layout> {
+    let layout = match typ {
+      Type::Void => self.void,
+      Type::Bool => self.bool,
+      Type::Char | Type::UChar => self.char,
+      Type::Short | Type::UShort => self.short,
+      Type::Int | Type::UInt => self.int,
+      Type::Long | Type::ULong => self.long,
+      Type::LongLong | Type::ULongLong => self.long_long,
+      Type::Pointer(_) => self.pointer,
+      Type::Array(elem, len) => {
+        let elem = self.checked_layout(elem)?;
+        Layout::new(elem.size.checked_mul(*len)?, elem.align)
+      }
+    };
+    (layout.size <= i64::MAX as usize).then_some(layout)
+  }
+
+  pub fn size_of(&self, typ: &Type) -> usize {
+    self.layout(typ).size
+  }
+
+  pub fn align_of(&self, typ: &Type) -> usize {
+    self.layout(typ).align
+  }
+
+  /// Returns the integer constant `value` converted to `typ`, i.e. truncated
+  /// to its size and extended back by its signedness.
+  pub fn convert(&self, value: i64, typ: &Type) -> i64 {
+    match self.size_of(typ) {
+      _ if *typ == Type::Bool => (value != 0) as i64,
+      1 if typ.is_unsigned() => value as u8 as i64,
+      1 => value as i8 as i64,
+      2 if typ.is_unsigned() => value as u16 as i64,
+      2 => value as i16 as i64,
+      4 if typ.is_unsigned() => value as u32 as i64,
+      4 => value as i32 as i64,
+      _ => value,
+    }
+  }
+}
+
+#[test]
+fn test_layout() {
+  let layout = &X86_64_SYSV;
+  assert_eq!(layout.layout(&Type::Char), Layout::new(1, 1));
+  assert_eq!(layout.layout(&Type::Bool), Layout::new(1, 1));
+  assert_eq!(layout.layout(&Type::UShort), Layout::new(2, 2));
+  assert_eq!(layout.layout(&Type::UInt), Layout::new(4, 4));
+  assert_eq!(layout.layout(&Type::LongLong), Layout::new(8, 8));
+  assert_eq!(
+    layout.layout(&Type::Pointer(Box::new(Type::Char))),
+    Layout::new(8, 8)
+  );
+  let matrix = Type::Array(Box::new(Type::Array(Box::new(Type::Int), 3)), 2);
+  assert_eq!(layout.layout(&matrix), Layout::new(24, 4));
+  assert_eq!(
+    layout.layout(&Type::Array(Box::new(Type::Char), 0)),
+    Layout::new(0, 1)
+  );
+  let huge = Type::Array(Box::new(Type::Int), 1 << 62);
+  assert_eq!(layout.checked_layout(&huge), None);
+  assert_eq!(layout.checked_layout(&Type::Array(Box::new(huge), 0)), None);
+  assert_eq!(layout.convert(-1, &Type::UInt), 4294967295);
+  assert_eq!(layout.convert(4294967295, &Type::Int), -1);
+  assert_eq!(layout.convert(-1, &Type::ULong), -1);
+  assert_eq!(layout.convert(256, &Type::Bool), 1);
+}
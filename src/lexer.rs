@@ -1,26 +1,120 @@
+use crate::ast::Type;
+use crate::diagnostic::Diagnostic;
+use crate::source_map::SourceFile;
 use crate::token::{Position, Token, TokenList, TokenType};
 
 pub struct Lexer {
   input: Vec<char>,
   position: usize,
+  base: usize,
+  // offsets[i] is where input[i] was in the source before line splicing,
+  // with one more entry for the end of input
+  offsets: Vec<usize>,
 }
 
 fn is_number(c: char) -> bool {
   c.is_ascii_digit()
 }
 
+/// Whether `c` can begin an identifier. Only ASCII letters are accepted.
 fn is_alpha(c: char) -> bool {
-  c.is_alphabetic()
+  c.is_ascii_alphabetic() || c == '_'
+}
+
+/// Removes every backslash-newline pair (translation phase 2), returning the
+/// spliced chars and the original offset of each of them.
+fn splice_lines(source: Vec<char>) -> (Vec<char>, Vec<usize>) {
+  let mut input = Vec::with_capacity(source.len());
+  let mut offsets = Vec::with_capacity(source.len() + 1);
+  let mut i = 0;
+  while i < source.len() {
+    if source[i] == '\\' {
+      match source.get(i + 1) {
+        Some('\n') => {
+          i += 2;
+          continue;
+        }
+        Some('\r') if source.get(i + 2) == Some(&'\n') => {
+          i += 3;
+          continue;
+        }
+        _ => (),
+      }
+    }
+    input.push(source[i]);
+    offsets.push(i);
+    i += 1;
+  }
+  offsets.push(source.len());
+  (input, offsets)
+}
+
+/// Parses an integer suffix into whether it has 'u' and how many 'l's it has.
+fn parse_int_suffix(suffix: &str) -> Option<(bool, usize)> {
+  // the 'l's of 'll' must be in the same case
+  if suffix.contains("lL") || suffix.contains("Ll") {
+    return None;
+  }
+  match &*suffix.to_ascii_lowercase() {
+    "" => Some((false, 0)),
+    "u" => Some((true, 0)),
+    "l" => Some((false, 1)),
+    "ul" | "lu" => Some((true, 1)),
+    "ll" => Some((false, 2)),
+    "ull" | "llu" => Some((true, 2)),
+    _ => None,
+  }
+}
+
+/// Chooses the first type in the list of C11 6.4.4.1 that can represent `value`.
+fn int_literal_type(value: u64, decimal: bool, unsigned: bool, longs: usize) -> Option<Type> {
+  let candidates = match (unsigned, longs) {
+    (false, 0) if decimal => vec![Type::Int, Type::Long, Type::LongLong],
+    (false, 0) => vec![
+      Type::Int,
+      Type::UInt,
+      Type::Long,
+      Type::ULong,
+      Type::LongLong,
+      Type::ULongLong,
+    ],
+    (true, 0) => vec![Type::UInt, Type::ULong, Type::ULongLong],
+    (false, 1) if decimal => vec![Type::Long, Type::LongLong],
+    (false, 1) => vec![Type::Long, Type::ULong, Type::LongLong, Type::ULongLong],
+    (true, 1) => vec![Type::ULong, Type::ULongLong],
+    (false, _) if decimal => vec![Type::LongLong],
+    (false, _) => vec![Type::LongLong, Type::ULongLong],
+    (true, _) => vec![Type::ULongLong],
+  };
+  candidates.into_iter().find(|typ| {
+    let max = match typ {
+      Type::Int => i32::MAX as u64,
+      Type::UInt => u32::MAX as u64,
+      Type::Long | Type::LongLong => i64::MAX as u64,
+      _ => u64::MAX,
+    };
+    value <= max
+  })
 }
 
 impl Lexer {
-  pub fn new(input: Vec<char>) -> Lexer {
+  pub fn new(source: Vec<char>) -> Lexer {
+    let (input, offsets) = splice_lines(source);
     Lexer {
-      input: input,
+      input,
       position: 0,
+      base: 0,
+      offsets,
     }
   }
 
+  /// Creates a lexer for `file`, whose positions are offsets in the source map.
+  pub fn for_file(file: &SourceFile) -> Lexer {
+    let mut lexer = Lexer::new(file.contents.clone());
+    lexer.base = file.base;
+    lexer
+  }
+
   fn current(&self) -> Option<&char> {
     self.input.get(self.position)
   }
@@ -33,224 +127,319 @@ impl Lexer {
     self.input.get(self.position + 1)
   }
 
+  /// Makes the position of `input[start..end]` in the source map, undoing
+  /// line splicing.
+  fn span(&self, start: usize, end: usize) -> Position {
+    let source_end = if end > start {
+      self.offsets[end - 1] + 1
+    } else {
+      self.offsets[start]
+    };
+    Position::new(self.base + self.offsets[start], self.base + source_end)
+  }
+
   fn next(&mut self) {
     self.position += 1;
   }
 
   fn consume(&mut self, expect: &char) -> bool {
     match self.current() {
-      Some(next_char) => {
-        if next_char == expect {
+      Some(next_char) if next_char == expect => {
+        self.next();
+        true
+      }
+      _ => false,
+    }
+  }
+
+  /// Skips white spaces and comments, both of which separate tokens.
+  fn skip_space(&mut self) -> Result<(), Diagnostic> {
+    while let Some(cur) = self.current() {
+      if cur.is_whitespace() {
+        self.next();
+      } else if *cur == '/' && self.peek() == Some(&'/') {
+        while self.current().is_some() && self.current() != Some(&'\n') {
           self.next();
-          true
-        } else {
-          false
         }
+      } else if *cur == '/' && self.peek() == Some(&'*') {
+        self.skip_block_comment()?;
+      } else {
+        break;
       }
-      None => false,
     }
+    Ok(())
   }
 
-  fn skip_space(&mut self) {
-    while self.current().is_some() && self.current().unwrap().is_whitespace() {
+  fn skip_block_comment(&mut self) -> Result<(), Diagnostic> {
+    let pos = self.pos();
+    self.position += 2;
+    while let Some(cur) = self.current() {
+      if *cur == '*' && self.peek() == Some(&'/') {
+        self.position += 2;
+        return Ok(());
+      }
       self.next();
     }
+    Err(Diagnostic::error(
+      "unterminated comment",
+      self.span(pos, pos + 2),
+    ))
   }
 
-  pub fn tokenize(&mut self) -> TokenList {
+  pub fn tokenize(&mut self) -> Result<TokenList, Diagnostic> {
     let mut tokens = TokenList::new();
 
     while self.current().is_some() {
-      self.skip_space();
+      self.skip_space()?;
 
       if self.current().is_none() {
-        return tokens;
+        return Ok(tokens);
       }
 
-      match self.make_token() {
-        Some(tok) => tokens.push(tok),
-        None => tokens.push(Token::eof(self.position)),
-      }
+      tokens.push(self.make_token()?);
     }
 
-    tokens
+    Ok(tokens)
   }
 
-  fn make_token(&mut self) -> Option<Token> {
+  fn make_token(&mut self) -> Result<Token, Diagnostic> {
     let cur = self.current().unwrap();
-    let pos = self.pos();
 
     if is_number(*cur) {
-      Some(self.make_number())
+      self.make_number()
     } else if is_alpha(*cur) {
-      Some(self.make_id())
+      Ok(self.make_id())
+    } else if *cur == '\'' {
+      self.make_char()
+    } else if *cur == '"' {
+      self.make_string()
     } else {
-      match *cur {
-        '+' => {
-          self.next();
-          if self.consume(&'=') {
-            Some(Token {
-              ty: TokenType::Inc,
-              position: Position::new(pos, pos + 2),
-            })
-          } else {
-            Some(Token {
-              ty: TokenType::Plus,
-              position: Position::new(pos, pos + 1),
-            })
-          }
-        }
-        '-' => {
-          self.next();
-          if self.consume(&'=') {
-            Some(Token {
-              ty: TokenType::Dec,
-              position: Position::new(pos, pos + 2),
-            })
-          } else {
-            Some(Token {
-              ty: TokenType::Minus,
-              position: Position::new(pos, pos + 1),
-            })
-          }
-        }
-        '*' => {
-          self.next();
-          Some(Token {
-            ty: TokenType::Aster,
-            position: Position::new(pos, pos + 1),
-          })
-        }
-        '/' => {
-          self.next();
-          Some(Token {
-            ty: TokenType::Slash,
-            position: Position::new(pos, pos + 1),
-          })
-        }
-        '(' => {
-          self.next();
-          Some(Token {
-            ty: TokenType::LParen,
-            position: Position::new(pos, pos + 1),
-          })
-        }
-        ')' => {
-          self.next();
-          Some(Token {
-            ty: TokenType::RParen,
-            position: Position::new(pos, pos + 1),
-          })
-        }
-        '{' => {
-          self.next();
-          Some(Token {
-            ty: TokenType::LBrace,
-            position: Position::new(pos, pos + 1),
-          })
-        }
-        '}' => {
-          self.next();
-          Some(Token {
-            ty: TokenType::RBrace,
-            position: Position::new(pos, pos + 1),
-          })
-        }
-        '=' => {
-          self.next();
-          if self.consume(&'=') {
-            Some(Token {
-              ty: TokenType::Eq,
-              position: Position::new(pos, pos + 2),
-            })
-          } else {
-            Some(Token {
-              ty: TokenType::Assign,
-              position: Position::new(pos, pos + 1),
-            })
-          }
-        }
-        '!' => {
-          self.next();
-          if self.consume(&'=') {
-            Some(Token {
-              ty: TokenType::Ne,
-              position: Position::new(pos, pos + 2),
-            })
-          } else {
-            Some(Token {
-              ty: TokenType::Not,
-              position: Position::new(pos, pos + 1),
-            })
-          }
-        }
-        '<' => {
-          self.next();
-          if self.consume(&'=') {
-            Some(Token {
-              ty: TokenType::Le,
-              position: Position::new(pos, pos + 2),
-            })
-          } else {
-            Some(Token {
-              ty: TokenType::Lt,
-              position: Position::new(pos, pos + 1),
-            })
-          }
-        }
-        '>' => {
-          self.next();
-          if self.consume(&'=') {
-            Some(Token {
-              ty: TokenType::Ge,
-              position: Position::new(pos, pos + 2),
-            })
-          } else {
-            Some(Token {
-              ty: TokenType::Gt,
-              position: Position::new(pos, pos + 1),
-            })
-          }
-        }
-        ';' => {
-          self.next();
-          Some(Token {
-            ty: TokenType::Semicolon,
-            position: Position::new(pos, pos + 1),
-          })
-        }
-        ',' => {
-          self.next();
-          Some(Token {
-            ty: TokenType::Comma,
-            position: Position::new(pos, pos + 1),
-          })
-        }
-        _ => panic!("Unknown char '{}' found...", *cur),
-      }
+      self.make_punctuator()
     }
   }
 
-  fn make_number(&mut self) -> Token {
-    use std::iter::FromIterator;
-    let cur = self.current().unwrap();
+  /// Reads the longest punctuator at the current position, so that `a+++b`
+  /// is `a ++ + b` and `<<=` is never split into `<<` and `=`.
+  fn make_punctuator(&mut self) -> Result<Token, Diagnostic> {
     let pos = self.pos();
-    let mut num = vec![*cur];
+    for len in (1..=TokenType::MAX_PUNCTUATOR_LEN).rev() {
+      let candidate: String = match self.input.get(self.position..self.position + len) {
+        Some(chars) => chars.iter().collect(),
+        None => continue,
+      };
+      if let Some(ty) = TokenType::punctuator(&candidate) {
+        self.position += len;
+        return Ok(Token {
+          ty,
+          position: self.span(pos, pos + len),
+        });
+      }
+    }
+    Err(Diagnostic::error(
+      format!("unknown character '{}'", self.current().unwrap()),
+      self.span(pos, pos + 1),
+    ))
+  }
 
-    while let Some(next_char) = self.peek() {
-      if is_number(*next_char) {
-        num.push(*next_char);
+  fn make_number(&mut self) -> Result<Token, Diagnostic> {
+    let pos = self.pos();
+    let mut literal = String::new();
+    while let Some(cur) = self.current() {
+      if cur.is_ascii_alphanumeric() || *cur == '_' {
+        literal.push(*cur);
         self.next();
       } else {
         break;
       }
     }
-    match String::from_iter(num).parse::<u64>() {
-      Ok(n) => {
-        self.next();
-        return Token::num(n, pos, self.position);
+    let position = self.span(pos, self.position);
+
+    let (radix, prefix_len) = match literal.get(..2) {
+      Some("0x") | Some("0X") => (16, 2),
+      Some("0b") | Some("0B") => (2, 2),
+      _ if literal.starts_with('0') => (8, 1),
+      _ => (10, 0),
+    };
+    let body = &literal[prefix_len..];
+    // decimal digits are taken even if they are invalid in the radix so
+    // that "09" is reported as a wrong digit rather than a wrong suffix
+    let digits_len = body
+      .find(|c: char| !(c.is_ascii_digit() || c.is_digit(radix)))
+      .unwrap_or(body.len());
+    let (digits, suffix) = body.split_at(digits_len);
+
+    if let Some(c) = digits.chars().find(|c| !c.is_digit(radix)) {
+      let base = if radix == 8 { "octal" } else { "binary" };
+      return Err(Diagnostic::error(
+        format!("invalid digit '{}' in {} constant", c, base),
+        position,
+      ));
+    }
+    if digits.is_empty() && radix != 8 {
+      return Err(Diagnostic::error(
+        format!("invalid suffix '{}' on integer constant", &literal[1..]),
+        position,
+      ));
+    }
+    let (unsigned, longs) = match parse_int_suffix(suffix) {
+      Some(suffix) => suffix,
+      None => {
+        return Err(Diagnostic::error(
+          format!("invalid suffix '{}' on integer constant", suffix),
+          position,
+        ))
+      }
+    };
+
+    // a lone "0" is an octal literal without digits
+    let value = match u64::from_str_radix(if digits.is_empty() { "0" } else { digits }, radix) {
+      Ok(value) => value,
+      Err(_) => {
+        return Err(Diagnostic::error(
+          "integer literal is too large to be represented in any integer type",
+          position,
+        ))
+      }
+    };
+    match int_literal_type(value, radix == 10, unsigned, longs) {
+      Some(typ) => Ok(Token::num(value, typ, position)),
+      None => Err(Diagnostic::error(
+        "integer literal is too large to be represented in a signed integer type",
+        position,
+      )),
+    }
+  }
+
+  fn make_char(&mut self) -> Result<Token, Diagnostic> {
+    let pos = self.pos();
+    self.next();
+    let mut bytes = Vec::new();
+    while !self.consume(&'\'') {
+      match self.current() {
+        Some('\n') | None => {
+          return Err(Diagnostic::error(
+            "missing terminating ' character",
+            self.span(pos, self.position),
+          ))
+        }
+        _ => self.read_literal_char(&mut bytes)?,
       }
-      Err(_) => panic!("Invalid number found..."),
+    }
+    let position = self.span(pos, self.position);
+    match bytes[..] {
+      [value] => Ok(Token {
+        ty: TokenType::Char(value),
+        position,
+      }),
+      [] => Err(Diagnostic::error("empty character constant", position)),
+      _ => Err(Diagnostic::error(
+        "multi-character character constant is not supported",
+        position,
+      )),
+    }
+  }
+
+  fn make_string(&mut self) -> Result<Token, Diagnostic> {
+    let pos = self.pos();
+    self.next();
+    let mut bytes = Vec::new();
+    while !self.consume(&'"') {
+      match self.current() {
+        Some('\n') | None => {
+          return Err(Diagnostic::error(
+            "missing terminating '\"' character",
+            self.span(pos, self.position),
+          ))
+        }
+        _ => self.read_literal_char(&mut bytes)?,
+      }
+    }
+    Ok(Token {
+      ty: TokenType::Str(bytes),
+      position: self.span(pos, self.position),
+    })
+  }
+
+  /// Reads a char of a character or string literal into `bytes`, decoding
+  /// escape sequences. Other chars are stored in UTF-8.
+  fn read_literal_char(&mut self, bytes: &mut Vec<u8>) -> Result<(), Diagnostic> {
+    let pos = self.pos();
+    let cur = *self.current().unwrap();
+    self.next();
+    if cur != '\\' {
+      let mut buf = [0; 4];
+      bytes.extend_from_slice(cur.encode_utf8(&mut buf).as_bytes());
+      return Ok(());
+    }
+
+    let escaped = match self.current() {
+      Some(c) => *c,
+      None => {
+        return Err(Diagnostic::error(
+          "incomplete escape sequence",
+          self.span(pos, self.position),
+        ))
+      }
+    };
+    self.next();
+    let value = match escaped {
+      'n' => b'\n',
+      't' => b'\t',
+      'r' => b'\r',
+      'a' => 0x07,
+      'b' => 0x08,
+      'f' => 0x0c,
+      'v' => 0x0b,
+      '\\' | '\'' | '"' | '?' => escaped as u8,
+      '0'..='7' => {
+        // up to 3 octal digits
+        let mut value = escaped.to_digit(8).unwrap();
+        for _ in 0..2 {
+          match self.current().and_then(|c| c.to_digit(8)) {
+            Some(digit) => {
+              value = value * 8 + digit;
+              self.next();
+            }
+            None => break,
+          }
+        }
+        self.escaped_byte(value, pos)?
+      }
+      'x' => {
+        let mut value: u32 = 0;
+        let mut num_digits = 0;
+        while let Some(digit) = self.current().and_then(|c| c.to_digit(16)) {
+          value = value.saturating_mul(16).saturating_add(digit);
+          num_digits += 1;
+          self.next();
+        }
+        if num_digits == 0 {
+          return Err(Diagnostic::error(
+            "\\x used with no following hex digits",
+            self.span(pos, self.position),
+          ));
+        }
+        self.escaped_byte(value, pos)?
+      }
+      _ => {
+        return Err(Diagnostic::error(
+          format!("unknown escape sequence '\\{}'", escaped),
+          self.span(pos, self.position),
+        ))
+      }
+    };
+    bytes.push(value);
+    Ok(())
+  }
+
+  fn escaped_byte(&self, value: u32, pos: usize) -> Result<u8, Diagnostic> {
+    if value > 0xff {
+      Err(Diagnostic::error(
+        "escape sequence out of range",
+        self.span(pos, self.position),
+      ))
+    } else {
+      Ok(value as u8)
     }
   }
 
@@ -261,7 +450,7 @@ impl Lexer {
     let mut id = vec![*cur];
 
     while let Some(next_char) = self.peek() {
-      if next_char.is_alphanumeric() {
+      if is_alpha(*next_char) || is_number(*next_char) {
         id.push(*next_char);
         self.next();
       } else {
@@ -273,36 +462,12 @@ impl Lexer {
     let pos_end = self.position;
 
     let id_str = String::from_iter(id);
-    match self.make_reserved_word(&id_str[..], pos, pos_end) {
-      Some(token) => token,
-      None => Token::id(id_str, pos, pos_end),
-    }
-  }
-
-  fn make_reserved_word(&mut self, id_str: &str, pos: usize, pos_end: usize) -> Option<Token> {
-    match id_str {
-      "if" => Some(Token {
-        ty: TokenType::If,
-        position: Position::new(pos, pos_end),
-      }),
-      "else" => Some(Token {
-        ty: TokenType::Else,
-        position: Position::new(pos, pos_end),
-      }),
-      "for" => Some(Token {
-        ty: TokenType::For,
-        position: Position::new(pos, pos_end),
-      }),
-      "while" => Some(Token {
-        ty: TokenType::While,
-        position: Position::new(pos, pos_end),
-      }),
-      "return" => Some(Token {
-        ty: TokenType::Return,
-        position: Position::new(pos, pos_end),
-      }),
-      "int" => Some(Token::typ("int", pos, pos_end)),
-      _ => None,
+    match TokenType::keyword(&id_str) {
+      Some(ty) => Token {
+        ty,
+        position: self.span(pos, pos_end),
+      },
+      None => Token::id(id_str, self.span(pos, pos_end)),
     }
   }
 }
@@ -338,8 +503,174 @@ fn test_lexer() {
   );
 }
 
+#[test]
+fn test_lexer_punctuator() {
+  test_tokenize("%", r#"TokenList[Token('%', @[0,1])]"#);
+  test_tokenize("...", r#"TokenList[Token('...', @[0,3])]"#);
+  test_tokenize(">>=", r#"TokenList[Token('>>=', @[0,3])]"#);
+  test_tokenize(
+    "a+++b",
+    r#"TokenList[Token(Id(a), @[0,1]), Token('++', @[1,3]), Token('+', @[3,4]), Token(Id(b), @[4,5])]"#,
+  );
+  test_tokenize(
+    "<<=<<<",
+    r#"TokenList[Token('<<=', @[0,3]), Token('<<', @[3,5]), Token('<', @[5,6])]"#,
+  );
+  test_tokenize(
+    "&&&|||",
+    r#"TokenList[Token('&&', @[0,2]), Token('&', @[2,3]), Token('||', @[3,5]), Token('|', @[5,6])]"#,
+  );
+  test_tokenize(
+    "p->x..y",
+    r#"TokenList[Token(Id(p), @[0,1]), Token('->', @[1,3]), Token(Id(x), @[3,4]), Token('.', @[4,5]), Token('.', @[5,6]), Token(Id(y), @[6,7])]"#,
+  );
+  test_tokenize(
+    "a[i]?b:~c",
+    r#"TokenList[Token(Id(a), @[0,1]), Token('[', @[1,2]), Token(Id(i), @[2,3]), Token(']', @[3,4]), Token('?', @[4,5]), Token(Id(b), @[5,6]), Token(':', @[6,7]), Token('~', @[7,8]), Token(Id(c), @[8,9])]"#,
+  );
+  // a line splice inside a punctuator still makes one token spanning it
+  test_tokenize("<\\\n<=", r#"TokenList[Token('<<=', @[0,5])]"#);
+}
+
+#[test]
+fn test_lexer_number() {
+  test_tokenize("0", r#"TokenList[Token(Num(0), @[0,1])]"#);
+  test_tokenize("0x1F", r#"TokenList[Token(Num(31), @[0,4])]"#);
+  test_tokenize("017", r#"TokenList[Token(Num(15), @[0,3])]"#);
+  test_tokenize("0b101", r#"TokenList[Token(Num(5), @[0,5])]"#);
+  test_tokenize("7u", r#"TokenList[Token(Num(7: unsigned int), @[0,2])]"#);
+  test_tokenize("7L", r#"TokenList[Token(Num(7: long), @[0,2])]"#);
+  test_tokenize(
+    "7uLL",
+    r#"TokenList[Token(Num(7: unsigned long long), @[0,4])]"#,
+  );
+  test_tokenize(
+    "7llu",
+    r#"TokenList[Token(Num(7: unsigned long long), @[0,4])]"#,
+  );
+  test_tokenize(
+    "2147483648",
+    r#"TokenList[Token(Num(2147483648: long), @[0,10])]"#,
+  );
+  test_tokenize(
+    "0x80000000",
+    r#"TokenList[Token(Num(2147483648: unsigned int), @[0,10])]"#,
+  );
+  test_tokenize(
+    "0xFFFFFFFFFFFFFFFF",
+    r#"TokenList[Token(Num(18446744073709551615: unsigned long), @[0,18])]"#,
+  );
+}
+
+#[test]
+fn test_lexer_char() {
+  test_tokenize("'a'", r#"TokenList[Token(Char('a'), @[0,3])]"#);
+  test_tokenize(
+    r"'\n' '\\' '\''",
+    r#"TokenList[Token(Char('\n'), @[0,4]), Token(Char('\\'), @[5,9]), Token(Char('\''), @[10,14])]"#,
+  );
+  test_tokenize(
+    r"'\0' '\101' '\x41' '\xff'",
+    r#"TokenList[Token(Char('\u{0}'), @[0,4]), Token(Char('A'), @[5,11]), Token(Char('A'), @[12,18]), Token(Char('\u{ff}'), @[19,25])]"#,
+  );
+  test_tokenize_error("''", "error@[0,2]: empty character constant");
+  test_tokenize_error(
+    "'ab'",
+    "error@[0,4]: multi-character character constant is not supported",
+  );
+  test_tokenize_error("'a", "error@[0,2]: missing terminating ' character");
+  test_tokenize_error(r"'\q'", r"error@[1,3]: unknown escape sequence '\q'");
+  test_tokenize_error(r"'\400'", "error@[1,5]: escape sequence out of range");
+  test_tokenize_error(
+    r"'\x'",
+    r"error@[1,3]: \x used with no following hex digits",
+  );
+}
+
+#[test]
+fn test_lexer_string() {
+  test_tokenize(r#""""#, r#"TokenList[Token(Str(""), @[0,2])]"#);
+  test_tokenize(
+    r#""%d\n" "a\"b\x41\0""#,
+    r#"TokenList[Token(Str("%d\n"), @[0,6]), Token(Str("a\"bA\u{0}"), @[7,19])]"#,
+  );
+  test_tokenize(
+    r#""// /* */""#,
+    r#"TokenList[Token(Str("// /* */"), @[0,10])]"#,
+  );
+  test_tokenize_error(
+    "\"abc\n\"",
+    "error@[0,4]: missing terminating '\"' character",
+  );
+}
+
+#[test]
+fn test_lexer_comment() {
+  test_tokenize(
+    "1 // one\n+ 2",
+    r#"TokenList[Token(Num(1), @[0,1]), Token('+', @[9,10]), Token(Num(2), @[11,12])]"#,
+  );
+  test_tokenize(
+    "1 /* one\n * / */+/**/2",
+    r#"TokenList[Token(Num(1), @[0,1]), Token('+', @[16,17]), Token(Num(2), @[21,22])]"#,
+  );
+  test_tokenize("// one \\\n+ 2\n", r#"TokenList[]"#);
+  test_tokenize(
+    "12\\\n3 /\\\r\n* one */ <\\\n=",
+    r#"TokenList[Token(Num(123), @[0,5]), Token('<=', @[19,23])]"#,
+  );
+}
+
+#[test]
+fn test_lexer_keyword() {
+  test_tokenize(
+    "_start my_var x1 _",
+    r#"TokenList[Token(Id(_start), @[0,6]), Token(Id(my_var), @[7,13]), Token(Id(x1), @[14,16]), Token(Id(_), @[17,18])]"#,
+  );
+  test_tokenize(
+    "int char void _Bool sizeof integer",
+    r#"TokenList[Token('int', @[0,3]), Token('char', @[4,8]), Token('void', @[9,13]), Token('_Bool', @[14,19]), Token('sizeof', @[20,26]), Token(Id(integer), @[27,34])]"#,
+  );
+  test_tokenize(
+    "_Static_assert switch static const",
+    r#"TokenList[Token('_Static_assert', @[0,14]), Token('switch', @[15,21]), Token('static', @[22,28]), Token('const', @[29,34])]"#,
+  );
+  test_tokenize_error("a\u{e9}", "error@[1,2]: unknown character '\u{e9}'");
+}
+
+#[test]
+fn test_lexer_error() {
+  test_tokenize_error("1 $ 2", "error@[2,3]: unknown character '$'");
+  test_tokenize_error("1 /* 2 */ /* 3", "error@[10,12]: unterminated comment");
+  test_tokenize_error(
+    "99999999999999999999",
+    "error@[0,20]: integer literal is too large to be represented in any integer type",
+  );
+  test_tokenize_error(
+    "9223372036854775808",
+    "error@[0,19]: integer literal is too large to be represented in a signed integer type",
+  );
+  test_tokenize_error("0189", "error@[0,4]: invalid digit '8' in octal constant");
+  test_tokenize_error("0b12", "error@[0,4]: invalid digit '2' in binary constant");
+  test_tokenize_error("0x", "error@[0,2]: invalid suffix 'x' on integer constant");
+  test_tokenize_error(
+    "12lul",
+    "error@[0,5]: invalid suffix 'lul' on integer constant",
+  );
+  test_tokenize_error(
+    "12lL",
+    "error@[0,4]: invalid suffix 'lL' on integer constant",
+  );
+}
+
 #[cfg(test)]
 fn test_tokenize(input: &str, expected: &str) {
   let mut lexer = Lexer::new(input.chars().collect());
-  assert_eq!(format!("{}", lexer.tokenize()), expected);
+  assert_eq!(format!("{}", lexer.tokenize().unwrap()), expected);
+}
+
+#[cfg(test)]
+fn test_tokenize_error(input: &str, expected: &str) {
+  let mut lexer = Lexer::new(input.chars().collect());
+  assert_eq!(format!("{}", lexer.tokenize().unwrap_err()), expected);
 }
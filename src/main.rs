@@ -2,32 +2,49 @@ extern crate clap;
 
 mod ast;
 mod codegen;
+mod diagnostic;
 // mod ir;
+mod layout;
 mod lexer;
 mod parser;
+mod source_map;
 mod token;
 mod utils;
+mod validator;
 
 use clap::{App, Arg};
-use std::path::{Path, PathBuf};
+use std::path::Path;
 use std::process::Command;
 
 use codegen::x86::Codegen;
+use diagnostic::Diagnostic;
 use lexer::Lexer;
 use parser::Parser;
+use source_map::{SourceFile, SourceMap};
+use validator::ModuleValidator;
 
-fn compile(source_file_path: &str, tmp_dir: &PathBuf) -> PathBuf {
-    let contents = std::fs::read_to_string(source_file_path).expect("[error] read_to_string");
-    let mut lexer = Lexer::new(contents.chars().collect());
-    let token_list = lexer.tokenize();
+fn compile(file: &SourceFile) -> Result<Codegen, Vec<Diagnostic>> {
+    let mut lexer = Lexer::for_file(file);
+    let token_list = lexer.tokenize().map_err(|diag| vec![diag])?;
     // println!("{}", token_list);
     let mut parser = Parser::new(token_list);
-    let module = parser.parse();
+    let (module, errors) = parser.parse();
+    if !errors.is_empty() {
+        return Err(errors);
+    }
+    let errors = ModuleValidator::new(&module).check();
+    if !errors.is_empty() {
+        return Err(errors);
+    }
     // println!("{}", module);
     let mut gen = Codegen::new();
-    let tmp_asm_path = tmp_dir.join("tmp.s");
-    gen.export(&tmp_asm_path, module);
-    tmp_asm_path
+    gen.run(*module).map_err(|diag| vec![diag])?;
+    Ok(gen)
+}
+
+fn exit_with_error(message: &str) -> ! {
+    eprintln!("[Error] {}", message);
+    std::process::exit(1);
 }
 
 fn main() -> std::io::Result<()> {
@@ -55,23 +72,40 @@ fn main() -> std::io::Result<()> {
     //     ))
     // };
     let tmp_elf_path = tmp_dir.join("tmp.elf");
+    let mut source_map = SourceMap::new();
 
     let mut cmd = Command::new("gcc");
     cmd.arg("-g").arg("-O0").arg("-o").arg(&tmp_elf_path);
 
-    for source_file_path in source_file_paths {
+    for (i, source_file_path) in source_file_paths.into_iter().enumerate() {
         let extension = Path::new(source_file_path)
             .extension()
             .expect("file was given without extenstion...");
         if extension == "c" {
-            let tmp_asm_path = compile(source_file_path, &tmp_dir);
+            let contents = std::fs::read_to_string(source_file_path)
+                .unwrap_or_else(|err| exit_with_error(&format!("{}: {}", source_file_path, err)));
+            let file = source_map.add_file(source_file_path, &contents);
+            let gen = compile(file).unwrap_or_else(|diags| {
+                for diag in diags.iter() {
+                    eprint!("{}", source_map.render(diag));
+                }
+                std::process::exit(1);
+            });
+            // each translation unit gets its own file, as they are all linked together
+            let tmp_asm_path = tmp_dir.join(format!("tmp{}.s", i));
+            gen.export(&tmp_asm_path).unwrap_or_else(|err| {
+                exit_with_error(&format!("{}: {}", tmp_asm_path.display(), err))
+            });
             cmd.arg(&tmp_asm_path);
         } else {
-            cmd.arg(&source_file_path);
+            cmd.arg(source_file_path);
         }
     }
 
-    cmd.status().expect("[Error] failed in the compilation...");
+    let status = cmd.status().expect("[Error] failed in the compilation...");
+    if !status.success() {
+        exit_with_error("failed in the compilation...");
+    }
     let status = Command::new(&tmp_elf_path)
         .status()
         .expect("[Error] failed to run elf...");
@@ -79,3 +113,347 @@ fn main() -> std::io::Result<()> {
 
     Ok(())
 }
+
+#[test]
+fn test_codegen() {
+    // a 'case' labels only the statement after it
+    assert_eq!(
+        test_run(
+            "switch_case_body",
+            "int main() { int x = 2; switch (x) case 1: return 5; return 7; }"
+        ),
+        7
+    );
+    // 'unsigned int' arithmetic wraps around at 32 bits
+    assert_eq!(
+        test_run("unsigned_shift", "int main() { return ~0u >> 28; }"),
+        15
+    );
+    assert_eq!(
+        test_run(
+            "unsigned_arith",
+            "int main() { int x = -8; \
+             if ((0u - 1) != 4294967295u) return 1; if (x / 2u != 2147483644u) return 2; \
+             if (3000000000u + 3000000000u != 1705032704u) return 3; if (-1u != 4294967295u) return 4; \
+             if ((1 << 31) >= 0) return 5; if (x >> 1 != -4) return 6; \
+             if (3000000000u * 2u / 2u != 852516352u) return 7; return 0; }"
+        ),
+        0
+    );
+    // file-scope initializers are converted to the variable type
+    assert_eq!(
+        test_run(
+            "global_init_convert",
+            "char c = 300; char d = -1; int main() { return c + d; }"
+        ),
+        43
+    );
+    // libc is called through its usual prototypes
+    assert_eq!(
+        test_run(
+            "libc_prototypes",
+            "int puts(const char *s); void exit(int status); \
+             void *memset(void *restrict s, int c, int n); \
+             void set(int *const p) { *p = 4; return; } \
+             int main() { const int x = 1; int a[2]; set(&a[0]); memset(&a[1], 0, 4); \
+             puts(\"hi\"); exit(a[0] + a[1] + x); }"
+        ),
+        5
+    );
+    // integer types narrower than 'int' are stored truncated
+    assert_eq!(
+        test_run(
+            "integer_types",
+            "short g = 70000; unsigned char h = -1; _Bool b = 7; \
+             long unsigned int twice(long x) { return x * 2; } \
+             int main() { long l = 1; unsigned short us = 65535; signed char sc = -1; \
+             _Bool t = 42; l = l << 40; us++; \
+             if (twice(l) != 2199023255552) return 1; if (g != 4464) return 2; \
+             if (h + 1 != 256 || sc != -1) return 3; if (us != 0) return 4; \
+             if (b + t != 2) return 5; if (sizeof(long long) != 8) return 6; \
+             if (sizeof(short) + _Alignof(short) != 4) return 7; return 0; }"
+        ),
+        0
+    );
+    // a label also labels only the statement after it
+    assert_eq!(
+        test_run(
+            "label_body",
+            "int main() { int y = 0; if (0) L: y = 5; return y; }"
+        ),
+        0
+    );
+    assert_eq!(
+        test_run(
+            "label_loop_body",
+            "int main() { int i = 0; while (i < 3) L: i++; return i; }"
+        ),
+        3
+    );
+    // dense cases go through a jump table, with values out of its range
+    // going past the switch when there is no 'default'
+    assert_eq!(
+        test_run(
+            "switch_jump_table",
+            "int f(int x) { int r = 0; switch (x) { case -2: r = 1; break; case -1: r = 2; \
+             case 0: r += 3; break; case 1: r = 4; break; case 2: r = 5; } return r; } \
+             int main() { if (f(-3) != 0) return 1; if (f(-2) != 1) return 2; \
+             if (f(-1) != 5) return 3; if (f(0) != 3) return 4; if (f(1) != 4) return 5; \
+             if (f(2) != 5) return 6; if (f(3) != 0) return 7; return 0; }"
+        ),
+        0
+    );
+    // sparse cases are compared one by one
+    assert_eq!(
+        test_run(
+            "switch_compare_chain",
+            "int f(int x) { switch (x) { case -1000: return 1; case 7: return 2; \
+             default: return 3; case 100000: return 4; } } \
+             int main() { if (f(-1000) != 1) return 1; if (f(7) != 2) return 2; \
+             if (f(100000) != 4) return 3; if (f(8) != 3) return 4; \
+             switch (5) { case 1: return 5; case 50: return 6; } return 0; }"
+        ),
+        0
+    );
+    // 'sizeof expr' is a constant of the declared type of 'expr', whose
+    // operand isn't evaluated
+    assert_eq!(
+        test_run(
+            "sizeof_expr",
+            "int a[4]; int b = sizeof a; int c[sizeof a]; long f(void); \
+             int g(int *p) { char a = 1; switch (sizeof a) { case sizeof p: return 0; case 1: ; } \
+             { long a; if (sizeof a != 8) return 0; } \
+             return sizeof p + sizeof a + sizeof f() + sizeof a++ + a; } \
+             int main() { return b + sizeof c + g(0); }"
+        ),
+        99
+    );
+    // 'continue' in a 'for' still runs the step expression
+    assert_eq!(
+        test_run(
+            "for_continue_step",
+            "int main() { int i; int s = 0; \
+             for (i = 0; i < 10; i++) { if (i % 2) continue; s += i; } return s + i; }"
+        ),
+        30
+    );
+    // 'case' values are converted to the promoted type of the controlling
+    // expression
+    assert_eq!(
+        test_run(
+            "switch_case_convert",
+            "int f(unsigned x) { switch (x) { case -1: return 1; default: return 2; } } \
+             int g(char c) { switch (c) { case 255: return 3; case -1: return 4; } return 5; } \
+             int main() { return f(4294967295u) * 10 + g(-1); }"
+        ),
+        14
+    );
+    // the right-hand side of '&&' and '||' only runs when it decides the result
+    assert_eq!(
+        test_run(
+            "short_circuit",
+            "int n; int bump() { n++; return 1; } \
+             int main() { int i = 0; int r = 0 && bump(); r += 1 || bump(); \
+             r += (i = 1) && (i = 5); r += 0 || ++i; r += 1 && ++i; r += 0 && i++; \
+             return n * 100 + i * 10 + r; }"
+        ),
+        74
+    );
+    // postfix operators give the old value and prefix ones the new value
+    assert_eq!(
+        test_run(
+            "inc_dec_value",
+            "int main() { int i = 5; int a = i++; int b = ++i; int c = i--; int d = --i; \
+             if (a != 5 || b != 7 || c != 7 || d != 5 || i != 5) return 1; \
+             char ch = 127; if (ch++ != 127 || ch != -128) return 2; return 0; }"
+        ),
+        0
+    );
+    // the left-hand side of a compound assignment is evaluated only once
+    assert_eq!(
+        test_run(
+            "compound_assign_once",
+            "int main() { int a[3]; int i = 0; a[0] = 10; a[1] = 20; a[2] = 30; \
+             a[i++] += 1; a[i++] *= 2; if (i != 2) return 1; \
+             if (a[0] != 11 || a[1] != 40 || a[2] != 30) return 2; \
+             int *p = a; *p++ -= 1; if (p != &a[1] || a[0] != 10) return 3; return 0; }"
+        ),
+        0
+    );
+    // '?:' evaluates only the branch it selects
+    assert_eq!(
+        test_run(
+            "conditional_branch",
+            "int main() { int a = 0, b = 0; int x = 1 ? a++ : b++; int y = 0 ? a++ : b--; \
+             int z = a ? 7 : (b = 9); if (x != 0 || y != 0 || z != 7) return 1; \
+             if (a != 1 || b != -1) return 2; return 0; }"
+        ),
+        0
+    );
+    // initialized globals start with their values and the others with zero
+    assert_eq!(
+        test_run(
+            "global_vars",
+            "int a = 5; int b; char c = 'x'; long d = -3; int *p; \
+             void add(int n) { b += n; } \
+             int main() { if (b != 0 || p != 0) return 1; add(a); add(a); \
+             if (b != 10 || c != 120 || d != -3) return 2; p = &b; *p = 1; return b + a; }"
+        ),
+        6
+    );
+    // pointer arithmetic counts in pointees, and so does a pointer difference
+    assert_eq!(
+        test_run(
+            "pointer_arith",
+            "int main() { long a[4]; char s[4]; long *p = &a[0]; long *q = p + 3; \
+             char *c = &s[1]; if (q - p != 3 || p - q != -3) return 1; \
+             if (&a[2] - 1 != &a[1] || 2 + p != &a[2]) return 3; \
+             *(p + 1) = 42; q -= 2; if (*q != 42) return 4; \
+             if (c + 2 - &s[0] != 3) return 5; return 0; }"
+        ),
+        0
+    );
+    // arrays decay to pointers to their first elements, and 'a[i][j]' indexes
+    // the rows of a 2-D array
+    assert_eq!(
+        test_run(
+            "array_index",
+            "int sum(int *p, int n) { int s = 0; int i; for (i = 0; i < n; i++) s += p[i]; return s; } \
+             int main() { int m[2][3]; int i, j; \
+             for (i = 0; i < 2; i++) for (j = 0; j < 3; j++) m[i][j] = i * 10 + j; \
+             int *row = m[1]; if (row[2] != 12 || *(*(m + 1) + 1) != 11) return 1; \
+             if (sizeof m != 24 || sizeof m[0] != 12 || sizeof row != 8) return 2; \
+             if (&m[1][0] - &m[0][0] != 3 || 2[row] != 12) return 3; \
+             return sum(m[0], 6); }"
+        ),
+        36
+    );
+    // return values are converted to the declared return type
+    assert_eq!(
+        test_run(
+            "return_convert",
+            "_Bool f(int x) { return x; } char g(int x) { return x; } \
+             unsigned char h(long x) { return x; } int k(long x) { return x; } \
+             _Bool b(_Bool v) { return v; } \
+             int main() { long big = 65536; big = big * 65536 + 1; \
+             if (f(7) != 1 || f(256) != 1 || f(0) != 0) return 1; \
+             if (g(300) != 44 || g(200) != -56) return 2; \
+             if (h(-1) != 255 || k(big) != 1) return 3; \
+             if (b(256) != 1 || b(0) != 0) return 4; \
+             return 42; }"
+        ),
+        42
+    );
+}
+
+#[test]
+fn test_codegen_error() {
+    assert_eq!(
+        test_compile_error("int f(int a, int b, int c, int d, int e, int f, int g) { return g; }"),
+        vec!["error@[52,53]: too many parameters in definition of 'f'\n  note: at most 6 arguments can be passed in registers"]
+    );
+    assert_eq!(
+        test_compile_error("void v; int main() { return 0; }"),
+        vec!["error@[5,6]: variable has incomplete type 'void'"]
+    );
+    assert_eq!(
+        test_compile_error("int x; char *x;"),
+        vec!["error@[13,14]: redefinition of 'x' with a different type: 'char *' vs 'int'\n  note@[4,5]: previous definition is here"]
+    );
+    assert_eq!(
+        test_compile_error("int f; int f() { return 0; }"),
+        vec!["error@[11,12]: redefinition of 'f' as different kind of symbol\n  note@[4,5]: previous definition is here"]
+    );
+    assert_eq!(
+        test_compile_error("int g(void); int g;"),
+        vec!["error@[17,18]: redefinition of 'g' as different kind of symbol\n  note@[4,5]: previous definition is here"]
+    );
+    assert_eq!(
+        test_compile_error("int main() { return sizeof x; }"),
+        vec!["error@[27,28]: use of undeclared identifier 'x'"]
+    );
+    assert_eq!(
+        test_compile_error("int main() { int a = 1; { int b = 2; } return a + b; }"),
+        vec!["error@[50,51]: use of undeclared identifier 'b'"]
+    );
+    for (input, expected) in [
+        (
+            "int main() { int a[2]; int *p = a, *q = a + 1; p -= q; return 0; }",
+            "error@[49,51]: incompatible integer to pointer conversion assigning to 'int *' from 'long'",
+        ),
+        (
+            "int main() { int a[2]; int i = 0; i += a; return i; }",
+            "error@[36,38]: incompatible pointer to integer conversion assigning to 'int' from 'int *'",
+        ),
+        (
+            "int main() { int a; void *vp = &a; int x = *vp; return x; }",
+            "error@[43,44]: initializing 'int' with an expression of incompatible type 'void'",
+        ),
+        (
+            "void f(void) {} int main() { int x = f(); return x; }",
+            "error@[37,38]: initializing 'int' with an expression of incompatible type 'void'",
+        ),
+        (
+            "void f(void) {} int main() { if (f()) return 1; return f(); }",
+            "error@[33,34]: statement requires expression of scalar type ('void' invalid)",
+        ),
+        (
+            "void f(void) {} int main() { return f() + 1; }",
+            "error@[40,41]: invalid operands to binary expression ('void' and 'int')",
+        ),
+    ] {
+        assert_eq!(test_compile_error(input), vec![expected], "{}", input);
+    }
+    assert_eq!(
+        test_compile_error("int main() { char a[2147483647]; char b[2]; return 0; }"),
+        vec!["error@[38,39]: total size of local variables is too large"]
+    );
+    assert_eq!(
+        test_compile_error("int f(void p[]);"),
+        vec!["error@[12,13]: array has incomplete element type 'void'"]
+    );
+    assert_eq!(
+        test_compile_error("int f(void x); int g(int, void);"),
+        vec![
+            "error@[11,12]: argument may not have 'void' type",
+            "error@[26,30]: 'void' must be the first and only parameter if specified",
+        ]
+    );
+}
+
+/// Compiles `input`, which must fail, and returns the diagnostics.
+#[cfg(test)]
+fn test_compile_error(input: &str) -> Vec<String> {
+    let mut source_map = SourceMap::new();
+    let file = source_map.add_file("error.c", input);
+    match compile(file) {
+        Ok(_) => panic!("{} compiled", input),
+        Err(diags) => diags.iter().map(|diag| format!("{}", diag)).collect(),
+    }
+}
+
+/// Compiles `input` into an executable called `name`, runs it and returns
+/// its exit status.
+#[cfg(test)]
+fn test_run(name: &str, input: &str) -> i32 {
+    let mut source_map = SourceMap::new();
+    let file = source_map.add_file(&format!("{}.c", name), input);
+    let gen = compile(file).unwrap_or_else(|diags| {
+        let diags: Vec<String> = diags.iter().map(|diag| source_map.render(diag)).collect();
+        panic!("{}", diags.concat())
+    });
+
+    let dir = std::env::temp_dir().join(format!("mycc-test-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let asm_path = dir.join(format!("{}.s", name));
+    let elf_path = dir.join(name);
+    gen.export(&asm_path).unwrap();
+    let status = Command::new("gcc")
+        .arg("-o")
+        .arg(&elf_path)
+        .arg(&asm_path)
+        .status()
+        .unwrap();
+    assert!(status.success());
+    Command::new(&elf_path).status().unwrap().code().unwrap()
+}
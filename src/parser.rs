@@ -1,27 +1,49 @@
 use crate::ast::*;
+use crate::diagnostic::Diagnostic;
+use crate::layout::X86_64_SYSV;
+#[cfg(test)]
 use crate::lexer::Lexer;
 use crate::token::{Position, Token, TokenList, TokenType};
+use std::collections::HashMap;
 
 #[derive(PartialOrd, PartialEq)]
 enum Precedence {
-  LOWEST = 0x0,
-  ASSIGN = 0x1,
-  COMPARE = 0x2,
-  SUM = 0x3,
-  PRODUCT = 0x4,
-  PREFIX = 0x5,
+  Lowest = 0x0,
+  Comma = 0x1,
+  Assign = 0x2,
+  Conditional = 0x3,
+  LogicalOr = 0x4,
+  LogicalAnd = 0x5,
+  BitOr = 0x6,
+  BitXor = 0x7,
+  BitAnd = 0x8,
+  Equality = 0x9,
+  Relational = 0xa,
+  Shift = 0xb,
+  Sum = 0xc,
+  Product = 0xd,
+  Prefix = 0xe,
 }
 
 pub struct Parser {
   token_list: TokenList,
   index: usize,
+  errors: Vec<Diagnostic>,
+  /// The types of the variables declared so far, the globals first and the
+  /// innermost block last, for typing the operand of 'sizeof'.
+  scopes: Vec<HashMap<String, Type>>,
+  /// The return types of the functions declared so far.
+  functions: HashMap<String, Type>,
 }
 
 impl Parser {
   pub fn new(token_list: TokenList) -> Parser {
     Parser {
-      token_list: token_list,
+      token_list,
       index: 0,
+      errors: Vec::new(),
+      scopes: vec![HashMap::new()],
+      functions: HashMap::new(),
     }
   }
 
@@ -33,45 +55,56 @@ impl Parser {
     }
   }
 
-  fn current_or_panic(&self) -> &Token {
-    if self.index < self.token_list.len() {
-      &self.token_list[self.index]
-    } else {
-      panic!("Unexpectedly reached to <EOF>...");
+  fn current_or_error(&self) -> Result<&Token, Diagnostic> {
+    match self.current() {
+      Some(token) => Ok(token),
+      None => Err(Diagnostic::error(
+        "unexpected end of file",
+        self.eof_position(),
+      )),
     }
   }
 
   fn peek(&self) -> Option<&Token> {
-    if self.index < self.token_list.len() {
+    if self.index + 1 < self.token_list.len() {
       Some(&self.token_list[self.index + 1])
     } else {
       None
     }
   }
 
-  fn peek_is(&self, expect_type: TokenType) -> bool {
-    if self.peek().is_none() {
-      return false;
+  fn current_is(&self, expect_type: TokenType) -> bool {
+    match self.current() {
+      Some(current) => current.ty == expect_type,
+      None => false,
     }
-    let peek = self.peek().unwrap();
-    peek.ty == expect_type
   }
 
-  fn peek_is_id(&self) -> bool {
-    if self.peek().is_none() {
-      return false;
-    }
-    let peek = self.peek().unwrap();
-    match peek.ty {
-      TokenType::Id(_) => true,
-      _ => false,
+  fn peek_is(&self, expect_type: TokenType) -> bool {
+    match self.peek() {
+      Some(peek) => peek.ty == expect_type,
+      None => false,
     }
   }
 
+  /// Whether the token `n` ahead of the current one starts a type.
+  fn nth_is_type(&self, n: usize) -> bool {
+    self.index + n < self.token_list.len() && self.token_list[self.index + n].ty.is_decl_specifier()
+  }
+
   fn on_eof(&self) -> bool {
     self.index >= self.token_list.len()
   }
 
+  /// Zero-width position just behind the last token, used for errors at <EOF>.
+  fn eof_position(&self) -> Position {
+    let end = match self.token_list.tokens.last() {
+      Some(token) => token.position.end,
+      None => 0,
+    };
+    Position::new(end, end)
+  }
+
   fn next(&mut self) {
     self.index += 1;
   }
@@ -89,293 +122,792 @@ impl Parser {
     }
   }
 
-  fn consume_or_panic(&mut self, expect_type: TokenType) -> &Token {
+  fn consume_or_error(&mut self, expect_type: TokenType) -> Result<&Token, Diagnostic> {
     if self.on_eof() {
-      panic!("Unexpectly reached to <EOF>...");
+      return Err(Diagnostic::error(
+        format!("expected {} but reached <EOF>", expect_type),
+        self.eof_position(),
+      ));
     }
     let current_token = &self.token_list[self.index];
     if current_token.ty == expect_type {
       self.index += 1;
-      current_token
+      Ok(current_token)
     } else {
-      panic!("Expect {} but {} found...", expect_type, current_token.ty);
+      Err(Diagnostic::error(
+        format!("expected {} but {} found", expect_type, current_token.ty),
+        current_token.position,
+      ))
     }
   }
 
-  pub fn parse(&mut self) -> Box<Module> {
+  /// Consumes the closing token of a bracketed construct, pointing back at the
+  /// opening one when it is missing.
+  fn consume_closing(
+    &mut self,
+    expect_type: TokenType,
+    open_position: Position,
+  ) -> Result<&Token, Diagnostic> {
+    let open = match expect_type {
+      TokenType::RParen => TokenType::LParen,
+      TokenType::RBrace => TokenType::LBrace,
+      _ => return self.consume_or_error(expect_type),
+    };
+    self
+      .consume_or_error(expect_type)
+      .map_err(|diag| diag.with_label(open_position, format!("to match this {}", open)))
+  }
+
+  /// Skips tokens after a syntax error in a statement until the parser is
+  /// likely to be at the start of the next one: just behind a `;` or a
+  /// `{ ... }` block, or in front of a `}` or a type keyword beginning a
+  /// statement.
+  fn synchronize_stmt(&mut self, start: usize) {
+    // make sure that the token the error was found at is never parsed again,
+    // unless it ends the statement or the enclosing block
+    match self.current() {
+      Some(token) if token.ty == TokenType::Semicolon || token.ty == TokenType::LBrace => {}
+      Some(token) if token.ty == TokenType::RBrace && self.index != start => {}
+      _ => self.next(),
+    }
+    while let Some(token) = self.current() {
+      match token.ty {
+        TokenType::Semicolon => {
+          self.next();
+          return;
+        }
+        TokenType::LBrace => {
+          self.skip_braces();
+          return;
+        }
+        TokenType::RBrace => return,
+        ref ty if ty.is_decl_specifier() && self.follows_stmt_end() => return,
+        _ => self.next(),
+      }
+    }
+  }
+
+  /// Skips tokens after a syntax error in a declaration until a type keyword
+  /// beginning the next one at file scope.
+  fn synchronize_decl(&mut self) {
+    // the token the error was found at is never parsed again, and a '{' is
+    // skipped with its block below
+    if !self.current_is(TokenType::LBrace) {
+      self.next();
+    }
+    while let Some(token) = self.current() {
+      match token.ty {
+        TokenType::LBrace => self.skip_braces(),
+        ref ty if ty.is_decl_specifier() && self.follows_stmt_end() => return,
+        _ => self.next(),
+      }
+    }
+  }
+
+  /// Whether the previous token ends a statement or a declaration, or opens a
+  /// block, so that the current one may begin the next.
+  fn follows_stmt_end(&self) -> bool {
+    self.index > 0
+      && matches!(
+        self.token_list[self.index - 1].ty,
+        TokenType::Semicolon | TokenType::LBrace | TokenType::RBrace
+      )
+  }
+
+  /// Skips a `{ ... }` group including nested ones.
+  fn skip_braces(&mut self) {
+    let mut depth = 0;
+    while let Some(token) = self.current() {
+      match token.ty {
+        TokenType::LBrace => depth += 1,
+        TokenType::RBrace => depth -= 1,
+        _ => (),
+      }
+      self.next();
+      if depth == 0 {
+        return;
+      }
+    }
+  }
+
+  /// Parses the whole token list. Syntax errors don't stop the parser: the
+  /// statements they occur in are dropped and parsing resumes at the next
+  /// one, so the returned module is partial whenever errors are returned.
+  pub fn parse(&mut self) -> (Box<Module>, Vec<Diagnostic>) {
     let mut module = Box::new(Module::new());
     while !self.on_eof() {
       match self.parse_decl() {
-        Some(stmt) => {
-          module.add_stmt(stmt);
+        Ok(stmt) => module.add_stmt(stmt),
+        Err(diag) => {
+          self.errors.push(diag);
+          // the blocks the error was found in are left
+          self.scopes.truncate(1);
+          self.synchronize_decl();
         }
-        None => break,
       }
     }
-    module
+    (module, std::mem::take(&mut self.errors))
   }
 
-  pub fn parse_decl(&mut self) -> Option<Box<Stmt>> {
-    let token = self.current()?;
+  pub fn parse_decl(&mut self) -> Result<Stmt, Diagnostic> {
+    let token = self.current_or_error()?;
     match token.ty {
-      TokenType::Type(_) => {
-        let (typ, name) = self.parse_var_decl();
-        let token = self.current()?;
+      ref ty if ty.is_decl_specifier() => {
+        let decl_position = token.position;
+        // 'extern' on a function changes nothing, as functions are external
+        // by default
+        let is_extern = self.consume(TokenType::Extern).is_some();
+        let base = self.parse_type()?;
+        let (typ, name, position) = self.parse_declarator(base.clone())?;
+        let token = self.current_or_error()?;
         match token.ty {
           TokenType::LParen => {
-            let args = self.parse_fn_args(); // consume '(' first_arg (, arg)* ')'
-            let block = self.parse_stmt_block();
-            Some(Box::new(Stmt::FnStmt {
-              name: name,
-              args: args,
+            let args = self.parse_fn_args()?; // consume '(' first_arg (, arg)* ')'
+            self.functions.insert(name.clone(), typ.clone());
+            if self.consume(TokenType::Semicolon).is_some() {
+              return Ok(Stmt::FnDecl {
+                name,
+                args,
+                ret_type: typ,
+                position,
+              });
+            }
+            // the parameters are in scope in the body
+            let params = args.container.iter();
+            self.scopes.push(
+              params
+                .map(|arg| (arg.name.clone(), arg.typ.clone()))
+                .collect(),
+            );
+            let block = self.parse_stmt_block()?;
+            self.scopes.pop();
+            Ok(Stmt::FnStmt {
+              name,
+              args,
               body: block,
               ret_type: typ,
-            }))
+              position,
+            })
           }
-          _ => panic!("var decl is not supported now..."),
+          _ => self.parse_declarators(base, typ, name, position, decl_position, is_extern),
         }
       }
-      _ => panic!("Expected decl but {} found...", token.ty),
+      _ => Err(Diagnostic::error(
+        format!("expected declaration but {} found", token.ty),
+        token.position,
+      )),
     }
   }
 
-  pub fn parse_var_decl(&mut self) -> (Type, String) {
-    let typ = self.parse_type();
-    let token = self.current_or_panic();
-    let name = token.get_id_string();
-    self.next();
-    (typ, name)
+  /// Parses a declarator, which makes the type of a declared name out of the
+  /// `base` type of the whole declaration, e.g. '*p' in 'int a, *p;'.
+  fn parse_declarator(&mut self, base: Type) -> Result<(Type, String, Position), Diagnostic> {
+    let typ = self.parse_pointer(base);
+    let (name, position) = self.parse_identifier()?;
+    let typ = self.parse_array_dims(typ)?;
+    Ok((typ, name, position))
   }
 
-  pub fn parse_type(&mut self) -> Type {
-    let token = self.current_or_panic();
-    let type_name = match &token.ty {
-      TokenType::Type(typ_name) => String::from(&*typ_name),
-      _ => panic!("Expected type-token but other token found..."),
-    };
-    self.next();
-    match &*type_name {
-      "int" => Type::Int,
-      _ => panic!("Invalid type name found..."),
+  /// Parses the '[len]'s behind a declared name. The first one is the
+  /// outermost, so 'int a[2][3]' is an array of 2 arrays of 3 ints.
+  fn parse_array_dims(&mut self, elem: Type) -> Result<Type, Diagnostic> {
+    let mut lens = Vec::new();
+    while let Some(token) = self.consume(TokenType::LBracket) {
+      let open_position = token.position;
+      let len = self.parse_expr(Precedence::Lowest)?;
+      let len_position = len.position();
+      let len = match len.const_value() {
+        Some(value) if value >= 0 => value as usize,
+        Some(_) => {
+          return Err(Diagnostic::error(
+            "array has a negative size",
+            len.position(),
+          ))
+        }
+        None => {
+          return Err(Diagnostic::error(
+            "variable length arrays are not supported",
+            len.position(),
+          ))
+        }
+      };
+      self.consume_closing(TokenType::RBracket, open_position)?;
+      lens.push((len, len_position));
+    }
+    if let Some((_, position)) = lens.first() {
+      if elem == Type::Void {
+        return Err(void_array_error(*position));
+      }
     }
+    let mut typ = elem;
+    for (len, position) in lens.into_iter().rev() {
+      typ = Type::Array(Box::new(typ), len);
+      if X86_64_SYSV.checked_layout(&typ).is_none() {
+        return Err(Diagnostic::error("array is too large", position));
+      }
+    }
+    Ok(typ)
   }
 
-  pub fn parse_fn_args(&mut self) -> ArgList {
-    self
-      .consume(TokenType::LParen)
-      .expect("Expect ( but not found...");
+  /// Parses the '*'s in front of a declared name.
+  fn parse_pointer(&mut self, base: Type) -> Type {
+    let mut typ = base;
+    while self.consume(TokenType::Aster).is_some() {
+      self.skip_qualifiers();
+      typ = Type::Pointer(Box::new(typ));
+    }
+    typ
+  }
 
-    // No argment patern: "{fn_name}()"
+  /// Skips type qualifiers, which don't change how values are stored here.
+  fn skip_qualifiers(&mut self) {
+    while self.current_is(TokenType::Const)
+      || self.current_is(TokenType::Volatile)
+      || self.current_is(TokenType::Restrict)
+    {
+      self.next();
+    }
+  }
+
+  /// Parses a type without a declared name, e.g. 'int *[3]'.
+  fn parse_type_name(&mut self) -> Result<Type, Diagnostic> {
+    let base = self.parse_type()?;
+    let typ = self.parse_pointer(base);
+    self.parse_array_dims(typ)
+  }
+
+  fn parse_identifier(&mut self) -> Result<(String, Position), Diagnostic> {
+    let token = self.current_or_error()?;
+    match token.get_id_string() {
+      Some(name) => {
+        let position = token.position;
+        self.next();
+        Ok((name, position))
+      }
+      None => Err(Diagnostic::error(
+        format!("expected identifier but {} found", token.ty),
+        token.position,
+      )),
+    }
+  }
+
+  /// Parses the specifiers of a type in any order, e.g. 'long unsigned',
+  /// mixed with type qualifiers.
+  pub fn parse_type(&mut self) -> Result<Type, Diagnostic> {
+    self.skip_qualifiers();
+    let token = self.current_or_error()?;
+    if !token.ty.is_decl_specifier() {
+      return Err(Diagnostic::error(
+        format!("expected type but {} found", token.ty),
+        token.position,
+      ));
+    }
+    let mut specifiers = Vec::new();
+    let mut typ = Type::Int;
+    while let Some(token) = self.current() {
+      match token.ty {
+        TokenType::Void
+        | TokenType::Bool
+        | TokenType::CharKw
+        | TokenType::Short
+        | TokenType::Int
+        | TokenType::Long
+        | TokenType::Signed
+        | TokenType::Unsigned => {
+          specifiers.push(token.ty.clone());
+          // a combination is invalid as soon as a specifier doesn't fit in
+          typ = match specifiers_type(&specifiers) {
+            Some(typ) => typ,
+            None => {
+              return Err(Diagnostic::error(
+                format!(
+                  "cannot combine {} with previous declaration specifiers",
+                  token.ty
+                ),
+                token.position,
+              ))
+            }
+          };
+        }
+        TokenType::Const | TokenType::Volatile | TokenType::Restrict => {}
+        ref ty if ty.is_decl_specifier() && *ty != TokenType::Extern => {
+          return Err(Diagnostic::error(
+            format!("{} is not supported yet", ty),
+            token.position,
+          ))
+        }
+        _ => break,
+      }
+      self.next();
+    }
+    Ok(typ)
+  }
+
+  pub fn parse_fn_args(&mut self) -> Result<ArgList, Diagnostic> {
+    let open_position = self.consume_or_error(TokenType::LParen)?.position;
+
+    // No argment patern: "{fn_name}()" or "{fn_name}(void)", which are the
+    // same as in C23
+    if self.current_is(TokenType::Void) && self.peek_is(TokenType::RParen) {
+      self.next();
+    }
     if self.consume(TokenType::RParen).is_some() {
-      return ArgList::new();
+      return Ok(ArgList::new());
     }
 
     let mut args = ArgList::new();
-    let first_arg = self.parse_arg();
+    let first_arg = self.parse_arg()?;
     args.push(first_arg);
 
-    // with argments patern: "{fn_name}(first_arg (, arg)*)"
+    // with argments patern: "{fn_name}(first_arg (, arg)* (, ...)?)"
     while self.consume(TokenType::RParen).is_none() {
-      self.consume_or_panic(TokenType::Comma);
-      let arg = self.parse_arg();
+      if self.consume(TokenType::Comma).is_none() {
+        self.consume_closing(TokenType::RParen, open_position)?;
+        break;
+      }
+      if self.consume(TokenType::Ellipsis).is_some() {
+        args.variadic = true;
+        self.consume_closing(TokenType::RParen, open_position)?;
+        break;
+      }
+      let arg = self.parse_arg()?;
       args.push(arg);
     }
 
-    args
+    Ok(args)
   }
 
-  pub fn parse_arg(&mut self) -> Arg {
-    let (typ, name) = self.parse_var_decl();
-    // let arg = self.current_or_panic();
-    // let arg_name = arg.get_id_string();
-    // self.next();
-    Arg {
-      name: name,
-      typ: typ,
+  pub fn parse_arg(&mut self) -> Result<Arg, Diagnostic> {
+    let position = self.current_or_error()?.position;
+    let base = self.parse_type()?;
+    let typ = self.parse_pointer(base);
+    // prototypes may leave the parameters unnamed
+    let (name, position) = match self.current() {
+      Some(token) if token.get_id_string().is_some() => self.parse_identifier()?,
+      _ => (String::new(), position),
+    };
+    // a parameter declared as an array is a pointer to its element, so the
+    // length may be left out
+    let typ = if self.current_is(TokenType::LBracket) && self.peek_is(TokenType::RBracket) {
+      if typ == Type::Void {
+        return Err(void_array_error(self.current_or_error()?.position));
+      }
+      self.next();
+      self.next();
+      Type::Pointer(Box::new(self.parse_array_dims(typ)?))
+    } else {
+      match self.parse_array_dims(typ)? {
+        Type::Array(elem, _) => Type::Pointer(elem),
+        typ => typ,
+      }
+    };
+    if typ == Type::Void {
+      let message = if name.is_empty() {
+        "'void' must be the first and only parameter if specified"
+      } else {
+        "argument may not have 'void' type"
+      };
+      return Err(Diagnostic::error(message, position));
     }
+    Ok(Arg {
+      name,
+      typ,
+      position,
+    })
   }
 
-  pub fn parse_stmt_block(&mut self) -> Vec<Box<Stmt>> {
-    self.consume_or_panic(TokenType::LBrace);
+  pub fn parse_stmt_block(&mut self) -> Result<Vec<Stmt>, Diagnostic> {
+    let open_position = self.consume_or_error(TokenType::LBrace)?.position;
+    self.scopes.push(HashMap::new());
     let mut stmt_block = Vec::new();
     while self.consume(TokenType::RBrace).is_none() {
+      if self.on_eof() {
+        self.consume_closing(TokenType::RBrace, open_position)?;
+      }
+      let start = self.index;
       match self.parse_stmt() {
-        Some(stmt) => {
-          stmt_block.push(stmt);
-        }
-        None => {
-          break;
+        Ok(stmt) => stmt_block.push(stmt),
+        Err(diag) => {
+          self.errors.push(diag);
+          self.synchronize_stmt(start);
         }
       }
     }
-    stmt_block
+    self.scopes.pop();
+    Ok(stmt_block)
   }
 
-  pub fn parse_stmt(&mut self) -> Option<Box<Stmt>> {
-    let token = self.current()?;
-    let stmt = match token.ty {
+  pub fn parse_stmt(&mut self) -> Result<Stmt, Diagnostic> {
+    let token = self.current_or_error()?;
+    match token.ty {
+      TokenType::LBrace => {
+        let position = token.position;
+        let body = self.parse_stmt_block()?;
+        Ok(Stmt::Block { body, position })
+      }
+      TokenType::Semicolon => {
+        let position = token.position;
+        self.next();
+        Ok(Stmt::Empty { position })
+      }
       TokenType::If => self.parse_if_stmt(),
       TokenType::For => self.parse_for_stmt(),
+      TokenType::While => self.parse_while_stmt(),
+      TokenType::Do => self.parse_do_while_stmt(),
+      TokenType::Switch => self.parse_switch_stmt(),
+      TokenType::Case => {
+        let position = self.current_or_error()?.position;
+        self.next();
+        // a constant expression can't have assignments or commas
+        let value = self.parse_expr(Precedence::Assign)?;
+        self.consume_or_error(TokenType::Colon)?;
+        let body = Box::new(self.parse_stmt()?);
+        Ok(Stmt::CaseStmt {
+          value,
+          body,
+          position,
+        })
+      }
+      TokenType::Default => {
+        let position = self.current_or_error()?.position;
+        self.next();
+        self.consume_or_error(TokenType::Colon)?;
+        let body = Box::new(self.parse_stmt()?);
+        Ok(Stmt::DefaultStmt { body, position })
+      }
+      TokenType::Id(_) if self.peek_is(TokenType::Colon) => {
+        let (name, position) = self.parse_identifier()?;
+        self.next();
+        let body = Box::new(self.parse_stmt()?);
+        Ok(Stmt::LabelStmt {
+          name,
+          body,
+          position,
+        })
+      }
+      TokenType::Goto => {
+        self.next();
+        let (name, position) = self.parse_identifier()?;
+        self.consume_or_error(TokenType::Semicolon)?;
+        Ok(Stmt::GotoStmt { name, position })
+      }
+      TokenType::Break => {
+        let position = self.jump_stmt_position()?;
+        Ok(Stmt::BreakStmt { position })
+      }
+      TokenType::Continue => {
+        let position = self.jump_stmt_position()?;
+        Ok(Stmt::ContinueStmt { position })
+      }
       TokenType::Return => self.parse_return_stmt(),
-      TokenType::Type(_) => {
-        let _ = self.parse_type();
-        // let (typ, var_name) = self.parse_var_decl();
-        // TODO: rewrite here for assign stmt..
-        let expr = self.parse_expr(Precedence::LOWEST)?;
-        self.consume_or_panic(TokenType::Semicolon);
-        Stmt::ExprStmt { expr: expr }
+      ref ty if ty.is_decl_specifier() => {
+        let decl_position = token.position;
+        let base = self.parse_type()?;
+        let (typ, name, position) = self.parse_declarator(base.clone())?;
+        self.parse_declarators(base, typ, name, position, decl_position, false)
       }
       _ => {
-        let expr = self.parse_expr(Precedence::LOWEST)?;
-        self.consume_or_panic(TokenType::Semicolon);
-        Stmt::ExprStmt { expr: expr }
+        let expr = self.parse_expr(Precedence::Lowest)?;
+        self.consume_or_error(TokenType::Semicolon)?;
+        Ok(Stmt::ExprStmt { expr })
       }
-    };
-    Some(Box::new(stmt))
+    }
   }
 
-  fn parse_if_stmt(&mut self) -> Stmt {
-    self.next();
-    self.consume_or_panic(TokenType::LParen);
-    let expr = self
-      .parse_expr(Precedence::LOWEST)
-      .expect("'if' must have the condition...");
-    self.consume_or_panic(TokenType::RParen);
-    let true_stmt_block = self.parse_stmt_block();
-    if true_stmt_block.len() == 0 {
-      panic!("'if' must have at least 1 statement...")
-    }
-
-    let false_stmt_block = match self.consume(TokenType::Else) {
-      Some(_) => {
-        let false_stmt_block = self.parse_stmt_block();
-        if false_stmt_block.len() == 0 {
-          panic!("'else' must have at least 1 statement...")
-        }
-        Some(false_stmt_block)
+  /// Parses the rest of 'type declarator (, declarator)* ;' after the first
+  /// declarator, each of which may have an '= initializer'. `base` is the
+  /// type before the declarators and `position` that of the whole declaration.
+  fn parse_declarators(
+    &mut self,
+    base: Type,
+    mut typ: Type,
+    mut name: String,
+    mut name_position: Position,
+    position: Position,
+    is_extern: bool,
+  ) -> Result<Stmt, Diagnostic> {
+    let mut decls = Vec::new();
+    loop {
+      // a name is in scope from its declarator on, so 'int n = sizeof n;' is
+      // fine
+      self
+        .scopes
+        .last_mut()
+        .unwrap()
+        .insert(name.clone(), typ.clone());
+      // an initializer can't have commas, which start the next declarator
+      let init = match self.consume(TokenType::Assign) {
+        Some(_) => Some(self.parse_expr(Precedence::Comma)?),
+        None => None,
+      };
+      decls.push(Declarator {
+        name,
+        typ,
+        init,
+        position: name_position,
+      });
+      if self.consume(TokenType::Comma).is_none() {
+        break;
       }
+      (typ, name, name_position) = self.parse_declarator(base.clone())?;
+    }
+    self.consume_or_error(TokenType::Semicolon)?;
+    Ok(Stmt::VarDecl {
+      decls,
+      is_extern,
+      position,
+    })
+  }
+
+  fn parse_if_stmt(&mut self) -> Result<Stmt, Diagnostic> {
+    let position = self.current_or_error()?.position;
+    self.next();
+    let open_position = self.consume_or_error(TokenType::LParen)?.position;
+    let expr = self.parse_expr(Precedence::Lowest)?;
+    self.consume_closing(TokenType::RParen, open_position)?;
+    let true_body = Box::new(self.parse_stmt()?);
+    // a dangling 'else' binds to the innermost 'if', which is the one
+    // whose body was just parsed
+    let false_body = match self.consume(TokenType::Else) {
+      Some(_) => Some(Box::new(self.parse_stmt()?)),
       None => None,
     };
-    Stmt::IfStmt {
+    Ok(Stmt::IfStmt {
       cond: expr,
-      true_body: true_stmt_block,
-      false_body: false_stmt_block,
-    }
+      true_body,
+      false_body,
+      position,
+    })
   }
 
-  fn parse_for_stmt(&mut self) -> Stmt {
+  fn parse_for_stmt(&mut self) -> Result<Stmt, Diagnostic> {
+    let position = self.current_or_error()?.position;
     self.next();
-    self.consume_or_panic(TokenType::LParen);
-    let prologue = self.parse_expr(Precedence::LOWEST);
-    self.consume_or_panic(TokenType::Semicolon);
-    let condition = self.parse_expr(Precedence::LOWEST);
-    self.consume_or_panic(TokenType::Semicolon);
-    let epilogue = self.parse_expr(Precedence::LOWEST);
-    self.consume_or_panic(TokenType::RParen);
-    let body = self.parse_stmt_block();
-    if body.len() == 0 {
-      panic!("'for' must have at least 1 statement...")
-    }
-
-    Stmt::ForStmt {
+    let open_position = self.consume_or_error(TokenType::LParen)?.position;
+    let prologue = self.parse_opt_expr(TokenType::Semicolon)?;
+    self.consume_or_error(TokenType::Semicolon)?;
+    let condition = self.parse_opt_expr(TokenType::Semicolon)?;
+    self.consume_or_error(TokenType::Semicolon)?;
+    let epilogue = self.parse_opt_expr(TokenType::RParen)?;
+    self.consume_closing(TokenType::RParen, open_position)?;
+    let body = Box::new(self.parse_stmt()?);
+
+    Ok(Stmt::ForStmt {
       cond: condition,
-      prologue: prologue,
-      epilogue: epilogue,
-      body: body,
-    }
+      prologue,
+      epilogue,
+      body,
+      position,
+    })
+  }
+
+  fn parse_while_stmt(&mut self) -> Result<Stmt, Diagnostic> {
+    let position = self.current_or_error()?.position;
+    self.next();
+    let open_position = self.consume_or_error(TokenType::LParen)?.position;
+    let cond = self.parse_expr(Precedence::Lowest)?;
+    self.consume_closing(TokenType::RParen, open_position)?;
+    let body = Box::new(self.parse_stmt()?);
+
+    Ok(Stmt::WhileStmt {
+      cond,
+      body,
+      position,
+    })
+  }
+
+  fn parse_do_while_stmt(&mut self) -> Result<Stmt, Diagnostic> {
+    let position = self.current_or_error()?.position;
+    self.next();
+    let body = Box::new(self.parse_stmt()?);
+    self.consume_or_error(TokenType::While)?;
+    let open_position = self.consume_or_error(TokenType::LParen)?.position;
+    let cond = self.parse_expr(Precedence::Lowest)?;
+    self.consume_closing(TokenType::RParen, open_position)?;
+    self.consume_or_error(TokenType::Semicolon)?;
+
+    Ok(Stmt::DoWhileStmt {
+      body,
+      cond,
+      position,
+    })
   }
 
-  fn parse_return_stmt(&mut self) -> Stmt {
+  fn parse_switch_stmt(&mut self) -> Result<Stmt, Diagnostic> {
+    let position = self.current_or_error()?.position;
+    self.next();
+    let open_position = self.consume_or_error(TokenType::LParen)?.position;
+    let cond = self.parse_expr(Precedence::Lowest)?;
+    self.consume_closing(TokenType::RParen, open_position)?;
+    // an undeclared name in 'cond' is reported when its code is generated
+    let typ = cond.typ(self).unwrap_or(Type::Int).promoted();
+    let body = Box::new(self.parse_stmt()?);
+
+    Ok(Stmt::SwitchStmt {
+      cond,
+      typ,
+      body,
+      position,
+    })
+  }
+
+  /// Consumes a `break;` or `continue;` and returns the position of the keyword.
+  fn jump_stmt_position(&mut self) -> Result<Position, Diagnostic> {
+    let position = self.current_or_error()?.position;
+    self.next();
+    self.consume_or_error(TokenType::Semicolon)?;
+    Ok(position)
+  }
+
+  fn parse_return_stmt(&mut self) -> Result<Stmt, Diagnostic> {
+    let position = self.current_or_error()?.position;
     self.next();
     // parse 'return' stmt with the lhs
     if self.consume(TokenType::Semicolon).is_none() {
-      let expr = self
-        .parse_expr(Precedence::LOWEST)
-        .expect("'return' is followed by an unexpected expr...");
-      self.consume_or_panic(TokenType::Semicolon);
-      Stmt::ReturnStmt { expr: Some(expr) }
+      let expr = self.parse_expr(Precedence::Lowest)?;
+      self.consume_or_error(TokenType::Semicolon)?;
+      Ok(Stmt::ReturnStmt {
+        expr: Some(expr),
+        position,
+      })
     } else {
       // ';' is already consumed in the condition
-      Stmt::ReturnStmt { expr: None }
+      Ok(Stmt::ReturnStmt {
+        expr: None,
+        position,
+      })
     }
   }
 
-  fn parse_expr(&mut self, precedence: Precedence) -> Option<Box<Expr>> {
+  /// Parses an expression unless the next token is `terminator`, which marks
+  /// an omitted expression like the clauses of `for (;;)`.
+  fn parse_opt_expr(&mut self, terminator: TokenType) -> Result<Option<Box<Expr>>, Diagnostic> {
+    if self.current_or_error()?.ty == terminator {
+      Ok(None)
+    } else {
+      self.parse_expr(Precedence::Lowest).map(Some)
+    }
+  }
+
+  fn parse_expr(&mut self, precedence: Precedence) -> Result<Box<Expr>, Diagnostic> {
     let mut lhs = self.parse_unary_op()?;
     while !self.on_eof() && precedence < self.current_precedence() {
       lhs = self.parse_binary_op(lhs)?;
     }
-    Some(lhs)
+    Ok(lhs)
   }
 
-  fn parse_id(&mut self) -> Option<Box<Expr>> {
-    let token = self.current()?;
-    let pos = token.position;
-    let name = token.get_id_string();
+  fn parse_id(&mut self) -> Result<Box<Expr>, Diagnostic> {
+    let token = self.current_or_error()?;
+    let position = token.position;
+    let name = token.get_id_string().unwrap();
     self.next();
-    Some(Box::new(Expr::Id {
-      name: name,
-      position: pos,
-    }))
+    Ok(Box::new(Expr::Id { name, position }))
   }
 
-  fn parse_number(&mut self) -> Option<Box<Expr>> {
-    let token = self.current()?;
-    let pos = token.position;
+  fn parse_number(&mut self) -> Result<Box<Expr>, Diagnostic> {
+    let token = self.current_or_error()?;
+    let position = token.position;
     match token.ty {
-      TokenType::Number(v) => {
+      TokenType::Number(value, ref typ) => {
+        let typ = typ.clone();
         self.next();
-        Some(Box::new(Expr::Number {
-          value: v,
-          position: pos,
+        Ok(Box::new(Expr::Number {
+          value,
+          typ,
+          position,
         }))
       }
-      _ => None,
+      _ => Err(Diagnostic::error(
+        format!("expected number but {} found", token.ty),
+        position,
+      )),
+    }
+  }
+
+  /// Parses a string literal, concatenating adjacent ones like `"a" "b"`.
+  fn parse_string(&mut self) -> Result<Box<Expr>, Diagnostic> {
+    let mut value = Vec::new();
+    let mut position = self.current_or_error()?.position;
+    while let Some(token) = self.current() {
+      match &token.ty {
+        TokenType::Str(bytes) => {
+          value.extend_from_slice(bytes);
+          position.end = token.position.end;
+          self.next();
+        }
+        _ => break,
+      }
     }
+    Ok(Box::new(Expr::Str { value, position }))
   }
 
-  fn parse_call(&mut self) -> Option<Box<Expr>> {
-    let token = self.current()?;
-    let pos = token.position;
-    let name = token.get_id_string();
+  fn parse_call(&mut self) -> Result<Box<Expr>, Diagnostic> {
+    let token = self.current_or_error()?;
+    let position = token.position;
+    let name = token.get_id_string().unwrap();
     self.next();
-    let args = self.parse_call_args();
-    Some(Box::new(Expr::Call {
-      name: name,
-      args: args,
-      position: pos,
+    let args = self.parse_call_args()?;
+    Ok(Box::new(Expr::Call {
+      name,
+      args,
+      position,
     }))
   }
 
-  pub fn parse_call_args(&mut self) -> Vec<Box<Expr>> {
-    self
-      .consume(TokenType::LParen)
-      .expect("Expect '(' but not found...");
+  pub fn parse_call_args(&mut self) -> Result<Vec<Expr>, Diagnostic> {
+    let open_position = self.consume_or_error(TokenType::LParen)?.position;
 
     // No argment patern: "{fn_name}()"
     if self.consume(TokenType::RParen).is_some() {
-      return Vec::new();
+      return Ok(Vec::new());
     }
 
     let mut args = Vec::new();
-    let first_arg = self.parse_expr(Precedence::LOWEST).unwrap();
-    args.push(first_arg);
+    // commas separate the arguments rather than being operators
+    let first_arg = self.parse_expr(Precedence::Comma)?;
+    args.push(*first_arg);
 
     // with argments patern: "{fn_name}(first_arg (, arg)*)"
     while self.consume(TokenType::RParen).is_none() {
-      self.consume_or_panic(TokenType::Comma);
-      let arg = self.parse_expr(Precedence::LOWEST).unwrap();
-      args.push(arg);
+      if self.consume(TokenType::Comma).is_none() {
+        self.consume_closing(TokenType::RParen, open_position)?;
+        break;
+      }
+      let arg = self.parse_expr(Precedence::Comma)?;
+      args.push(*arg);
     }
 
-    args
+    Ok(args)
   }
 
-  fn parse_unary_op(&mut self) -> Option<Box<Expr>> {
-    let token = self.current()?;
+  fn parse_unary_op(&mut self) -> Result<Box<Expr>, Diagnostic> {
+    let token = self.current_or_error()?;
     match token.ty {
       TokenType::Plus => self.make_unary_op(UnaryOpType::Plus),
       TokenType::Minus => self.make_unary_op(UnaryOpType::Minus),
+      TokenType::Not => self.make_unary_op(UnaryOpType::Not),
+      TokenType::Tilde => self.make_unary_op(UnaryOpType::BitNot),
+      TokenType::Inc => self.make_unary_op(UnaryOpType::PreInc),
+      TokenType::Dec => self.make_unary_op(UnaryOpType::PreDec),
+      TokenType::Amp => self.make_unary_op(UnaryOpType::AddrOf),
+      TokenType::Aster => self.make_unary_op(UnaryOpType::Deref),
+      // 'sizeof (int)' takes a type name, but 'sizeof (x)' an expression
+      TokenType::Sizeof if self.peek_is(TokenType::LParen) && self.nth_is_type(2) => {
+        self.make_type_op(TypeOpType::Sizeof)
+      }
+      TokenType::Sizeof => self.make_sizeof(),
+      TokenType::Alignof => self.make_type_op(TypeOpType::Alignof),
+      _ => {
+        let expr = self.parse_primary()?;
+        self.parse_postfix_op(expr)
+      }
+    }
+  }
+
+  fn parse_primary(&mut self) -> Result<Box<Expr>, Diagnostic> {
+    let token = self.current_or_error()?;
+    match token.ty {
       TokenType::Id(_) => {
         if self.peek_is(TokenType::LParen) {
           self.parse_call()
@@ -383,86 +915,268 @@ impl Parser {
           self.parse_id()
         }
       }
-      TokenType::Number(_) => self.parse_number(),
+      TokenType::Number(..) => self.parse_number(),
+      TokenType::Char(value) => {
+        let position = token.position;
+        self.next();
+        Ok(Box::new(Expr::Char { value, position }))
+      }
+      TokenType::Str(_) => self.parse_string(),
       TokenType::LParen => self.parse_grouped_expr(),
-      _ => None,
+      _ => Err(Diagnostic::error(
+        format!("expected expression but {} found", token.ty),
+        token.position,
+      )),
+    }
+  }
+
+  /// Applies the postfix operators following `expr`, which bind tighter than
+  /// the prefix ones (`-x++` is `-(x++)`).
+  fn parse_postfix_op(&mut self, mut expr: Box<Expr>) -> Result<Box<Expr>, Diagnostic> {
+    while let Some(token) = self.current() {
+      let position = token.position;
+      let op = match token.ty {
+        TokenType::Inc => UnaryOpType::PostInc,
+        TokenType::Dec => UnaryOpType::PostDec,
+        TokenType::LBracket => {
+          expr = self.parse_subscript(expr)?;
+          continue;
+        }
+        _ => break,
+      };
+      self.next();
+      expr = Box::new(Expr::UnaryOp {
+        op,
+        rhs: expr,
+        position,
+      });
     }
+    Ok(expr)
   }
 
-  fn parse_binary_op(&mut self, lhs: Box<Expr>) -> Option<Box<Expr>> {
-    let token = self.current()?;
+  /// Parses '[index]' behind `array`, which is '*(array + index)' by
+  /// definition.
+  fn parse_subscript(&mut self, array: Box<Expr>) -> Result<Box<Expr>, Diagnostic> {
+    let position = self.consume_or_error(TokenType::LBracket)?.position;
+    let index = self.parse_expr(Precedence::Lowest)?;
+    self.consume_closing(TokenType::RBracket, position)?;
+    let address = Box::new(Expr::BinaryOp {
+      op: BinaryOpType::Add,
+      lhs: array,
+      rhs: index,
+      position,
+    });
+    Ok(Box::new(Expr::UnaryOp {
+      op: UnaryOpType::Deref,
+      rhs: address,
+      position,
+    }))
+  }
+
+  fn parse_binary_op(&mut self, lhs: Box<Expr>) -> Result<Box<Expr>, Diagnostic> {
+    let token = self.current_or_error()?;
     let op = match token.ty {
+      TokenType::Question => return self.parse_conditional(lhs),
+      TokenType::Comma => BinaryOpType::Comma,
       TokenType::Plus => BinaryOpType::Add,
       TokenType::Minus => BinaryOpType::Sub,
       TokenType::Aster => BinaryOpType::Mul,
       TokenType::Slash => BinaryOpType::Div,
+      TokenType::Percent => BinaryOpType::Mod,
+      TokenType::Amp => BinaryOpType::BitAnd,
+      TokenType::Pipe => BinaryOpType::BitOr,
+      TokenType::Caret => BinaryOpType::BitXor,
+      TokenType::Shl => BinaryOpType::Shl,
+      TokenType::Shr => BinaryOpType::Shr,
       TokenType::Assign => BinaryOpType::Assign,
-      TokenType::Inc => BinaryOpType::Inc,
-      TokenType::Dec => BinaryOpType::Dec,
+      TokenType::AddAssign => BinaryOpType::AddAssign,
+      TokenType::SubAssign => BinaryOpType::SubAssign,
+      TokenType::MulAssign => BinaryOpType::MulAssign,
+      TokenType::DivAssign => BinaryOpType::DivAssign,
+      TokenType::ModAssign => BinaryOpType::ModAssign,
+      TokenType::ShlAssign => BinaryOpType::ShlAssign,
+      TokenType::ShrAssign => BinaryOpType::ShrAssign,
+      TokenType::AndAssign => BinaryOpType::AndAssign,
+      TokenType::OrAssign => BinaryOpType::OrAssign,
+      TokenType::XorAssign => BinaryOpType::XorAssign,
       TokenType::Eq => BinaryOpType::Eq,
       TokenType::Ne => BinaryOpType::Ne,
       TokenType::Lt => BinaryOpType::Lt,
       TokenType::Le => BinaryOpType::Le,
       TokenType::Gt => BinaryOpType::Gt,
       TokenType::Ge => BinaryOpType::Ge,
-      _ => return Some(lhs),
+      TokenType::AndAnd => BinaryOpType::LogicalAnd,
+      TokenType::OrOr => BinaryOpType::LogicalOr,
+      _ => return Ok(lhs),
+    };
+    let position = token.position;
+    let precedence = match Self::token_precedence(token) {
+      // assignments are right-associative: 'a = b = c' is 'a = (b = c)'
+      Precedence::Assign => Precedence::Comma,
+      precedence => precedence,
     };
-    let pos = token.position;
-    let precedence = Self::token_precedence(token);
     self.next();
     let rhs = self.parse_expr(precedence)?;
-    Some(Box::new(Expr::BinaryOp {
-      op: op,
-      lhs: lhs,
-      rhs: rhs,
-      position: pos,
+    Ok(Box::new(Expr::BinaryOp {
+      op,
+      lhs,
+      rhs,
+      position,
     }))
   }
 
-  fn parse_grouped_expr(&mut self) -> Option<Box<Expr>> {
-    if self.consume(TokenType::LParen).is_none() {
-      panic!("Expected '(' but {} found...", self.current()?);
-    }
-    let expr = self.parse_expr(Precedence::LOWEST);
-    match self.consume(TokenType::RParen) {
-      Some(_) => expr,
-      None => panic!("Expected ')' but {} found...", self.current()?),
-    }
+  fn parse_conditional(&mut self, cond: Box<Expr>) -> Result<Box<Expr>, Diagnostic> {
+    let position = self.current_or_error()?.position;
+    self.next();
+    // C allows any expression between '?' and ':', even a comma one
+    let then_expr = self.parse_expr(Precedence::Lowest)?;
+    self.consume_or_error(TokenType::Colon)?;
+    // right-associative: 'a ? b : c ? d : e' is 'a ? b : (c ? d : e)'
+    let else_expr = self.parse_expr(Precedence::Assign)?;
+    Ok(Box::new(Expr::Conditional {
+      cond,
+      then_expr,
+      else_expr,
+      position,
+    }))
+  }
+
+  fn parse_grouped_expr(&mut self) -> Result<Box<Expr>, Diagnostic> {
+    let open_position = self.consume_or_error(TokenType::LParen)?.position;
+    let expr = self.parse_expr(Precedence::Lowest)?;
+    self.consume_closing(TokenType::RParen, open_position)?;
+    Ok(expr)
+  }
+
+  fn make_unary_op(&mut self, op: UnaryOpType) -> Result<Box<Expr>, Diagnostic> {
+    let token = self.current_or_error()?;
+    let position = token.position;
+    self.next();
+
+    let rhs = self.parse_expr(Precedence::Prefix)?;
+    Ok(Box::new(Expr::UnaryOp { op, rhs, position }))
   }
 
-  fn make_unary_op(&mut self, op: UnaryOpType) -> Option<Box<Expr>> {
-    let token = self.current()?;
-    let pos = token.position;
+  /// Parses 'sizeof expr', which is folded into the size of the type of
+  /// `expr`, as the operand isn't evaluated.
+  fn make_sizeof(&mut self) -> Result<Box<Expr>, Diagnostic> {
+    let token = self.current_or_error()?;
+    let position = token.position;
     self.next();
 
-    let rhs = self.parse_expr(Precedence::PREFIX)?;
-    Some(Box::new(Expr::UnaryOp {
-      op: op,
-      rhs: rhs,
-      position: pos,
+    let rhs = self.parse_expr(Precedence::Prefix)?;
+    let typ = rhs.typ(self)?;
+    Ok(Box::new(Expr::TypeOp {
+      op: TypeOpType::Sizeof,
+      typ,
+      position,
     }))
   }
 
+  fn make_type_op(&mut self, op: TypeOpType) -> Result<Box<Expr>, Diagnostic> {
+    let token = self.current_or_error()?;
+    let position = token.position;
+    self.next();
+
+    let open_position = self.consume_or_error(TokenType::LParen)?.position;
+    let typ = self.parse_type_name()?;
+    self.consume_closing(TokenType::RParen, open_position)?;
+    Ok(Box::new(Expr::TypeOp { op, typ, position }))
+  }
+
   fn current_precedence(&self) -> Precedence {
     Self::token_precedence(self.current().unwrap())
   }
 
   fn token_precedence(token: &Token) -> Precedence {
     match token.ty {
-      TokenType::Assign | TokenType::Inc | TokenType::Dec => Precedence::ASSIGN,
-      TokenType::Eq
-      | TokenType::Ne
-      | TokenType::Lt
-      | TokenType::Le
-      | TokenType::Gt
-      | TokenType::Ge => Precedence::COMPARE,
-      TokenType::Plus | TokenType::Minus => Precedence::SUM,
-      TokenType::Aster | TokenType::Slash => Precedence::PRODUCT,
-      _ => Precedence::LOWEST,
+      TokenType::Assign
+      | TokenType::AddAssign
+      | TokenType::SubAssign
+      | TokenType::MulAssign
+      | TokenType::DivAssign
+      | TokenType::ModAssign
+      | TokenType::ShlAssign
+      | TokenType::ShrAssign
+      | TokenType::AndAssign
+      | TokenType::OrAssign
+      | TokenType::XorAssign => Precedence::Assign,
+      TokenType::Comma => Precedence::Comma,
+      TokenType::Question => Precedence::Conditional,
+      TokenType::OrOr => Precedence::LogicalOr,
+      TokenType::AndAnd => Precedence::LogicalAnd,
+      TokenType::Pipe => Precedence::BitOr,
+      TokenType::Caret => Precedence::BitXor,
+      TokenType::Amp => Precedence::BitAnd,
+      TokenType::Eq | TokenType::Ne => Precedence::Equality,
+      TokenType::Lt | TokenType::Le | TokenType::Gt | TokenType::Ge => Precedence::Relational,
+      TokenType::Shl | TokenType::Shr => Precedence::Shift,
+      TokenType::Plus | TokenType::Minus => Precedence::Sum,
+      TokenType::Aster | TokenType::Slash | TokenType::Percent => Precedence::Product,
+      _ => Precedence::Lowest,
     }
   }
 }
 
+impl Symbols for Parser {
+  fn var_type(&self, name: &str) -> Option<Type> {
+    self
+      .scopes
+      .iter()
+      .rev()
+      .find_map(|scope| scope.get(name))
+      .cloned()
+  }
+
+  fn ret_type(&self, name: &str) -> Option<Type> {
+    self.functions.get(name).cloned()
+  }
+}
+
+fn void_array_error(position: Position) -> Diagnostic {
+  Diagnostic::error("array has incomplete element type 'void'", position)
+}
+
+/// Returns the type named by the type specifiers `specifiers`, or `None` if
+/// they don't go together. 'int' is implied when only sign or size is given,
+/// and 'signed char' is the same as 'char', which is signed here.
+fn specifiers_type(specifiers: &[TokenType]) -> Option<Type> {
+  let count = |ty: TokenType| specifiers.iter().filter(|s| **s == ty).count();
+  let signed = count(TokenType::Signed);
+  let unsigned = count(TokenType::Unsigned);
+  if signed + unsigned > 1 {
+    return None;
+  }
+  let typ = match (
+    count(TokenType::Void),
+    count(TokenType::Bool),
+    count(TokenType::CharKw),
+    count(TokenType::Short),
+    count(TokenType::Int),
+    count(TokenType::Long),
+  ) {
+    (1, 0, 0, 0, 0, 0) | (0, 1, 0, 0, 0, 0) if signed + unsigned > 0 => return None,
+    (1, 0, 0, 0, 0, 0) => Type::Void,
+    (0, 1, 0, 0, 0, 0) => Type::Bool,
+    (0, 0, 1, 0, 0, 0) => Type::Char,
+    (0, 0, 0, 1, 0 | 1, 0) => Type::Short,
+    (0, 0, 0, 0, 0 | 1, 0) => Type::Int,
+    (0, 0, 0, 0, 0 | 1, 1) => Type::Long,
+    (0, 0, 0, 0, 0 | 1, 2) => Type::LongLong,
+    _ => return None,
+  };
+  if unsigned == 0 {
+    return Some(typ);
+  }
+  Some(match typ {
+    Type::Char => Type::UChar,
+    Type::Short => Type::UShort,
+    Type::Int => Type::UInt,
+    Type::Long => Type::ULong,
+    _ => Type::ULongLong,
+  })
+}
+
 #[test]
 fn test_parser() {
   test_parse("1 + 2;", r#"Stmt(Add@[2,3]{Num@[0,1]{1}, Num@[4,5]{2}})"#);
@@ -470,13 +1184,195 @@ fn test_parser() {
     "-5 + (4 - 20) * 4;",
     r#"Stmt(Add@[3,4]{Minus@[0,1]{Num@[1,2]{5}}, Mul@[14,15]{Sub@[8,9]{Num@[6,7]{4}, Num@[10,12]{20}}, Num@[16,17]{4}}})"#,
   );
+  test_parse(
+    r#"f("a" "b\n"  "", "c");"#,
+    r#"Stmt(Call@[0,1]{'f',
+  0: Str@[2,15]{"ab\n"}
+  1: Str@[17,20]{"c"}
+})"#,
+  );
+  test_parse(
+    r"'a' == '\n';",
+    r#"Stmt(Eq@[4,6]{Char@[0,3]{'a'}, Char@[7,11]{'\n'}})"#,
+  );
+  test_parse(
+    "while (i < 3) { i += 1; }",
+    "While(Lt@[9,10]{Id@[7,8]{'i'}, Num@[11,12]{3}}) {\n  0: Stmt(AddAssign@[18,20]{Id@[16,17]{'i'}, Num@[21,22]{1}})\n}",
+  );
+  test_parse(
+    "do { f(); } while (0);",
+    "Do {\n  0: Stmt(Call@[5,6]{'f',\n})\n} While(Num@[19,20]{0})",
+  );
+  test_parse(
+    "while (1) { break; continue; }",
+    "While(Num@[7,8]{1}) {\n  0: Break\n  1: Continue\n}",
+  );
+  test_parse(
+    "switch (x) { case 1: case -2: f(); break; default: ; }",
+    "Switch(Id@[8,9]{'x'}) {\n  0: Case(Num@[18,19]{1}) Case(Minus@[26,27]{Num@[27,28]{2}}) Stmt(Call@[30,31]{'f',\n})\n  1: Break\n  2: Default Empty\n}",
+  );
+  test_parse(
+    "!a || b && c == 0;",
+    "Stmt(LogicalOr@[3,5]{Not@[0,1]{Id@[1,2]{'a'}}, LogicalAnd@[8,10]{Id@[6,7]{'b'}, Eq@[13,15]{Id@[11,12]{'c'}, Num@[16,17]{0}}}})",
+  );
+  test_parse(
+    "a | b ^ c & d == e < f << g + h % ~i;",
+    "Stmt(BitOr@[2,3]{Id@[0,1]{'a'}, BitXor@[6,7]{Id@[4,5]{'b'}, BitAnd@[10,11]{Id@[8,9]{'c'}, Eq@[14,16]{Id@[12,13]{'d'}, Lt@[19,20]{Id@[17,18]{'e'}, Shl@[23,25]{Id@[21,22]{'f'}, Add@[28,29]{Id@[26,27]{'g'}, Mod@[32,33]{Id@[30,31]{'h'}, BitNot@[34,35]{Id@[35,36]{'i'}}}}}}}}}})",
+  );
+  test_parse(
+    "1 << 2 >> 3 == 4 != 5;",
+    "Stmt(Ne@[17,19]{Eq@[12,14]{Shr@[7,9]{Shl@[2,4]{Num@[0,1]{1}, Num@[5,6]{2}}, Num@[10,11]{3}}, Num@[15,16]{4}}, Num@[20,21]{5}})",
+  );
+  test_parse(
+    "-x++ + ++y--;",
+    "Stmt(Add@[5,6]{Minus@[0,1]{PostInc@[2,4]{Id@[1,2]{'x'}}}, PreInc@[7,9]{PostDec@[10,12]{Id@[9,10]{'y'}}}})",
+  );
+  test_parse(
+    "a = b <<= c - 1;",
+    "Stmt(Assign@[2,3]{Id@[0,1]{'a'}, ShlAssign@[6,9]{Id@[4,5]{'b'}, Sub@[12,13]{Id@[10,11]{'c'}, Num@[14,15]{1}}}})",
+  );
+  test_parse(
+    "x = a ? b, c : d || e ? f : g, h;",
+    "Stmt(Comma@[29,30]{Assign@[2,3]{Id@[0,1]{'x'}, Conditional@[6,7]{Id@[4,5]{'a'}, Comma@[9,10]{Id@[8,9]{'b'}, Id@[11,12]{'c'}}, Conditional@[22,23]{LogicalOr@[17,19]{Id@[15,16]{'d'}, Id@[20,21]{'e'}}, Id@[24,25]{'f'}, Id@[28,29]{'g'}}}}, Id@[31,32]{'h'}})",
+  );
+  test_parse("retry: f();", "Label('retry') Stmt(Call@[7,8]{'f',\n})");
+  test_parse("goto retry;", "Goto('retry')");
+  test_parse(
+    "if (a) if (b) return 1; else ;",
+    "If(Id@[4,5]{'a'}) If(Id@[11,12]{'b'}) Return(Num@[21,22]{1}) Else Empty",
+  );
+  test_parse(
+    "int a = 1, b, c = a = 2;",
+    "VarDecl('a': int = Num@[8,9]{1}, 'b': int, 'c': int = Assign@[20,21]{Id@[18,19]{'a'}, Num@[22,23]{2}})",
+  );
+  test_parse(
+    "char *s, c, **t = &s;",
+    "VarDecl('s': char *, 'c': char, 't': char ** = AddrOf@[18,19]{Id@[19,20]{'s'}})",
+  );
+  test_parse(
+    "*p = *q * 2;",
+    "Stmt(Assign@[3,4]{Deref@[0,1]{Id@[1,2]{'p'}}, Mul@[8,9]{Deref@[5,6]{Id@[6,7]{'q'}}, Num@[10,11]{2}}})",
+  );
+  test_parse(
+    "int a[2][3], *b[4];",
+    "VarDecl('a': int [2][3], 'b': int *[4])",
+  );
+  test_parse(
+    "long unsigned int *a, b;",
+    "VarDecl('a': unsigned long *, 'b': unsigned long)",
+  );
+  test_parse(
+    "const void *const p, *volatile q;",
+    "VarDecl('p': void *, 'q': void *)",
+  );
+  test_parse(
+    "a[i][1];",
+    "Stmt(Deref@[4,5]{Add@[4,5]{Deref@[1,2]{Add@[1,2]{Id@[0,1]{'a'}, Id@[2,3]{'i'}}}, Num@[5,6]{1}}})",
+  );
+  test_parse(
+    "{ int a[2][3]; sizeof a[0]; }",
+    "{\n  0: VarDecl('a': int [2][3])\n  1: Stmt(Sizeof@[15,21]{int [3]})\n}",
+  );
+  test_parse(
+    "{ char x; sizeof(int *[3]) + _Alignof(char) * sizeof (x); }",
+    "{\n  0: VarDecl('x': char)\n  1: Stmt(Add@[27,28]{Sizeof@[10,16]{int *[3]}, Mul@[44,45]{Alignof@[29,37]{char}, Sizeof@[46,52]{char}}})\n}",
+  );
+  test_parse("{ { } ; }", "{\n  0: {\n}\n  1: Empty\n}");
+}
+
+#[test]
+fn test_parser_error() {
+  test_parse_error("1 + ;", "error@[4,5]: expected expression but ';' found");
+  test_parse_error(
+    "(1 + 2;",
+    "error@[6,7]: expected ')' but ';' found\n  note@[0,1]: to match this '('",
+  );
+  test_parse_error("1 + 2", "error@[5,5]: expected ';' but reached <EOF>");
+  test_parse_error(
+    "do { f(); } while (1)",
+    "error@[21,21]: expected ';' but reached <EOF>",
+  );
+  test_parse_error(
+    "x = return;",
+    "error@[4,10]: expected expression but 'return' found",
+  );
+  test_parse_error(
+    "unsigned signed x;",
+    "error@[9,15]: cannot combine 'signed' with previous declaration specifiers",
+  );
+  test_parse_error(
+    "int a[4611686018427387904];",
+    "error@[6,25]: array is too large",
+  );
+  test_parse_error("int a[2][1L << 62];", "error@[12,14]: array is too large");
+  test_parse_error(
+    "sizeof(int [4611686018427387904]);",
+    "error@[12,31]: array is too large",
+  );
+  test_parse_error(
+    "void x[3];",
+    "error@[7,8]: array has incomplete element type 'void'",
+  );
+  test_parse_error(
+    "sizeof (y + 1);",
+    "error@[8,9]: use of undeclared identifier 'y'",
+  );
+}
+
+#[test]
+fn test_parser_recovery() {
+  let input = "int f() { 1 + ; return 1; }\n\
+               int g( { }\n\
+               int main() { if (1 {} return (2; return 3; }";
+  let mut lexer = Lexer::new(input.chars().collect());
+  let mut parser = Parser::new(lexer.tokenize().unwrap());
+  let (module, errors) = parser.parse();
+  let errors: Vec<String> = errors.iter().map(|diag| diag.message.clone()).collect();
+  assert_eq!(
+    errors,
+    vec![
+      "expected expression but ';' found",
+      "expected type but '{' found",
+      "expected ')' but '{' found",
+      "expected ')' but ';' found",
+    ]
+  );
+  let names: Vec<String> = module
+    .stmt_list
+    .iter()
+    .map(|stmt| match stmt {
+      Stmt::FnStmt { name, body, .. } => format!("{}:{}", name, body.len()),
+      _ => String::from("?"),
+    })
+    .collect();
+  assert_eq!(names, vec!["f:1", "main:1"]);
+
+  // the token an error is found at is skipped even if it starts a type
+  for input in [
+    "int main() { return sizeof(float); }",
+    "int puts(struct s *s);",
+  ] {
+    let mut lexer = Lexer::new(input.chars().collect());
+    let mut parser = Parser::new(lexer.tokenize().unwrap());
+    let (_, errors) = parser.parse();
+    assert_eq!(errors.len(), 1, "{}", input);
+  }
 }
 
 #[cfg(test)]
 fn test_parse(input: &str, expected: &str) {
   let mut lexer = Lexer::new(input.chars().collect());
-  let token_list = lexer.tokenize();
+  let token_list = lexer.tokenize().unwrap();
+  let mut parser = Parser::new(token_list);
+  let stmt = parser.parse_stmt().unwrap();
+  assert_eq!(format!("{}", stmt), expected);
+}
+
+#[cfg(test)]
+fn test_parse_error(input: &str, expected: &str) {
+  let mut lexer = Lexer::new(input.chars().collect());
+  let token_list = lexer.tokenize().unwrap();
   let mut parser = Parser::new(token_list);
-  let module = parser.parse();
-  assert_eq!(format!("{}", module.stmt_list[0]), expected);
+  let diag = parser.parse_stmt().unwrap_err();
+  assert_eq!(format!("{}", diag), expected);
 }
@@ -0,0 +1,191 @@
+use crate::diagnostic::{Diagnostic, Severity};
+use crate::token::Position;
+use std::fmt;
+
+/// A source file registered in the `SourceMap`.
+///
+/// Positions handed out by the lexer are offsets into the whole map, so the
+/// file occupies `base..base + contents.len()`.
+pub struct SourceFile {
+  pub name: String,
+  pub contents: Vec<char>,
+  pub base: usize,
+  line_starts: Vec<usize>,
+}
+
+impl SourceFile {
+  fn new(name: &str, contents: &str, base: usize) -> Self {
+    let contents: Vec<char> = contents.chars().collect();
+    let mut line_starts = vec![0];
+    for (i, c) in contents.iter().enumerate() {
+      if *c == '\n' {
+        line_starts.push(i + 1);
+      }
+    }
+    SourceFile {
+      name: String::from(name),
+      contents,
+      base,
+      line_starts,
+    }
+  }
+
+  /// The offset just behind the last char, which is still a valid position (<EOF>).
+  fn end(&self) -> usize {
+    self.base + self.contents.len()
+  }
+
+  /// Returns the 0-origin line index that contains the file-relative `offset`.
+  fn line_index(&self, offset: usize) -> usize {
+    match self.line_starts.binary_search(&offset) {
+      Ok(line) => line,
+      Err(line) => line - 1,
+    }
+  }
+
+  /// Returns the contents of the 0-origin `line` without the line break.
+  fn line(&self, line: usize) -> &[char] {
+    let start = self.line_starts[line];
+    let mut end = match self.line_starts.get(line + 1) {
+      Some(next_start) => next_start - 1,
+      None => self.contents.len(),
+    };
+    while end > start && (self.contents[end - 1] == '\r' || self.contents[end - 1] == '\n') {
+      end -= 1;
+    }
+    &self.contents[start..end]
+  }
+}
+
+/// A resolved position, with 1-origin line and column as compilers print them.
+pub struct Location<'a> {
+  pub file: &'a SourceFile,
+  pub line: usize,
+  pub column: usize,
+}
+
+impl fmt::Display for Location<'_> {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    write!(f, "{}:{}:{}", self.file.name, self.line, self.column)
+  }
+}
+
+pub struct SourceMap {
+  files: Vec<SourceFile>,
+}
+
+impl SourceMap {
+  pub fn new() -> Self {
+    SourceMap { files: Vec::new() }
+  }
+
+  pub fn add_file(&mut self, name: &str, contents: &str) -> &SourceFile {
+    // leave a gap of 1 so that the <EOF> position of a file never
+    // overlaps with the first char of the next one
+    let base = match self.files.last() {
+      Some(file) => file.end() + 1,
+      None => 0,
+    };
+    self.files.push(SourceFile::new(name, contents, base));
+    self.files.last().unwrap()
+  }
+
+  fn file_of(&self, offset: usize) -> Option<&SourceFile> {
+    self
+      .files
+      .iter()
+      .find(|file| file.base <= offset && offset <= file.end())
+  }
+
+  pub fn lookup(&self, offset: usize) -> Option<Location<'_>> {
+    let file = self.file_of(offset)?;
+    let offset = offset - file.base;
+    let line = file.line_index(offset);
+    Some(Location {
+      file,
+      line: line + 1,
+      column: offset - file.line_starts[line] + 1,
+    })
+  }
+
+  /// Renders `diag` in the usual compiler style, quoting the source line and
+  /// underlining the span:
+  ///
+  /// ```text
+  /// foo.c:2:16: error: expected ')' but ';' found
+  ///   return (1 + 2;
+  ///                ^
+  /// ```
+  pub fn render(&self, diag: &Diagnostic) -> String {
+    let mut s = self.render_message(diag.severity, &diag.message, diag.position);
+    for label in &diag.labels {
+      s += &self.render_message(Severity::Note, &label.message, label.position);
+    }
+    for note in &diag.notes {
+      s += &format!("{}: {}\n", Severity::Note, note);
+    }
+    s
+  }
+
+  fn render_message(&self, severity: Severity, message: &str, position: Position) -> String {
+    let location = match self.lookup(position.start) {
+      Some(location) => location,
+      None => return format!("{}{}: {}\n", severity, position, message),
+    };
+    let line = location.file.line(location.line - 1);
+    let column = location.column - 1;
+
+    // keep tabs so that the caret lines up with the quoted source line
+    let mut marker: String = line
+      .iter()
+      .take(column)
+      .map(|c| if *c == '\t' { '\t' } else { ' ' })
+      .collect();
+    marker.push('^');
+    // spans running over the end of the line are only underlined up to it
+    let len = position.end.saturating_sub(position.start);
+    let visible = line.len().saturating_sub(column);
+    for _ in 1..len.min(visible) {
+      marker.push('~');
+    }
+
+    format!(
+      "{}: {}: {}\n{}\n{}\n",
+      location,
+      severity,
+      message,
+      line.iter().collect::<String>(),
+      marker
+    )
+  }
+}
+
+#[test]
+fn test_source_map() {
+  let mut source_map = SourceMap::new();
+  source_map.add_file("a.c", "int main() {\n  return 0;\n}\n");
+  let base = source_map.add_file("b.c", "x\n\ty = 1 $ 2;").base;
+
+  assert_eq!(format!("{}", source_map.lookup(0).unwrap()), "a.c:1:1");
+  assert_eq!(format!("{}", source_map.lookup(15).unwrap()), "a.c:2:3");
+  assert_eq!(
+    format!("{}", source_map.lookup(base + 2).unwrap()),
+    "b.c:2:1"
+  );
+
+  let diag = Diagnostic::error("unknown character '$'", Position::new(base + 9, base + 10));
+  assert_eq!(
+    source_map.render(&diag),
+    "b.c:2:8: error: unknown character '$'\n\ty = 1 $ 2;\n\t      ^\n"
+  );
+
+  let diag = Diagnostic::error("expression is not assignable", Position::new(15, 21))
+    .with_label(Position::new(0, 3), "in this function")
+    .with_note("see the manual");
+  assert_eq!(
+    source_map.render(&diag),
+    "a.c:2:3: error: expression is not assignable\n  return 0;\n  ^~~~~~\n\
+     a.c:1:1: note: in this function\nint main() {\n^~~\n\
+     note: see the manual\n"
+  );
+}
@@ -1,18 +1,61 @@
+use crate::ast::Type;
 use crate::utils::ToSimpleString;
 use std::cmp::PartialEq;
 use std::fmt;
 use std::ops::{Index, IndexMut};
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum TokenType {
-  Id(String),  // ([a-z|A-Z|_])([a-z|A-Z|_|0-9])*
-  Number(u64), // [0-9][0-9]*
-  Type(String),
-  If,        // 'if'
-  Else,      // 'else'
-  For,       // 'for'
-  While,     // 'while'
-  Return,    // 'return'
+  Id(String),        // ([a-z|A-Z|_])([a-z|A-Z|_|0-9])*
+  Number(u64, Type), // [0-9][0-9]*, 0x[0-9a-f]+, 0b[01]+, 0[0-7]* with suffix
+  Char(u8),          // '.' with escape sequences
+  Str(Vec<u8>),      // ".*" with escape sequences, without the trailing NUL
+  // keywords
+  Auto,         // 'auto'
+  Break,        // 'break'
+  Case,         // 'case'
+  CharKw,       // 'char'
+  Const,        // 'const'
+  Continue,     // 'continue'
+  Default,      // 'default'
+  Do,           // 'do'
+  Double,       // 'double'
+  Else,         // 'else'
+  Enum,         // 'enum'
+  Extern,       // 'extern'
+  Float,        // 'float'
+  For,          // 'for'
+  Goto,         // 'goto'
+  If,           // 'if'
+  Inline,       // 'inline'
+  Int,          // 'int'
+  Long,         // 'long'
+  Register,     // 'register'
+  Restrict,     // 'restrict'
+  Return,       // 'return'
+  Short,        // 'short'
+  Signed,       // 'signed'
+  Sizeof,       // 'sizeof'
+  Static,       // 'static'
+  Struct,       // 'struct'
+  Switch,       // 'switch'
+  Typedef,      // 'typedef'
+  Union,        // 'union'
+  Unsigned,     // 'unsigned'
+  Void,         // 'void'
+  Volatile,     // 'volatile'
+  While,        // 'while'
+  Alignas,      // '_Alignas'
+  Alignof,      // '_Alignof'
+  Atomic,       // '_Atomic'
+  Bool,         // '_Bool'
+  Complex,      // '_Complex'
+  Generic,      // '_Generic'
+  Imaginary,    // '_Imaginary'
+  Noreturn,     // '_Noreturn'
+  StaticAssert, // '_Static_assert'
+  ThreadLocal,  // '_Thread_local'
+  // punctuators
   Plus,      // '+'
   Minus,     // '-'
   Aster,     // '*'
@@ -22,8 +65,8 @@ pub enum TokenType {
   LBrace,    // '{'
   RBrace,    // '}'
   Assign,    // '='
-  Inc,       // '+='
-  Dec,       // '-='
+  AddAssign, // '+='
+  SubAssign, // '-='
   Not,       // '!'
   Eq,        // '=='
   Ne,        // '!='
@@ -33,20 +76,86 @@ pub enum TokenType {
   Ge,        // '>='
   Semicolon, // ';'
   Comma,     // ','
-  Eof,
+  Percent,   // '%'
+  Amp,       // '&'
+  Pipe,      // '|'
+  Caret,     // '^'
+  Tilde,     // '~'
+  Shl,       // '<<'
+  Shr,       // '>>'
+  AndAnd,    // '&&'
+  OrOr,      // '||'
+  Inc,       // '++'
+  Dec,       // '--'
+  Arrow,     // '->'
+  Dot,       // '.'
+  Question,  // '?'
+  Colon,     // ':'
+  LBracket,  // '['
+  RBracket,  // ']'
+  Ellipsis,  // '...'
+  MulAssign, // '*='
+  DivAssign, // '/='
+  ModAssign, // '%='
+  AndAssign, // '&='
+  OrAssign,  // '|='
+  XorAssign, // '^='
+  ShlAssign, // '<<='
+  ShrAssign, // '>>='
 }
 
 impl ToSimpleString for TokenType {
   fn to_simple_string(&self) -> String {
     match self {
       TokenType::Id(x) => format!("Id({})", x),
-      TokenType::Number(x) => format!("Num({})", x),
-      TokenType::Type(ty) => format!("Type({})", ty),
-      TokenType::If => format!("If"),
-      TokenType::Else => format!("Else"),
-      TokenType::For => format!("For"),
-      TokenType::While => format!("While"),
-      TokenType::Return => format!("Return"),
+      TokenType::Number(x, Type::Int) => format!("Num({})", x),
+      TokenType::Number(x, typ) => format!("Num({}: {})", x, typ),
+      TokenType::Char(x) => format!("Char('{}')", (*x as char).escape_default()),
+      TokenType::Str(x) => format!("Str(\"{}\")", escape_bytes(x)),
+      TokenType::Auto => String::from("'auto'"),
+      TokenType::Break => String::from("'break'"),
+      TokenType::Case => String::from("'case'"),
+      TokenType::CharKw => String::from("'char'"),
+      TokenType::Const => String::from("'const'"),
+      TokenType::Continue => String::from("'continue'"),
+      TokenType::Default => String::from("'default'"),
+      TokenType::Do => String::from("'do'"),
+      TokenType::Double => String::from("'double'"),
+      TokenType::Else => String::from("'else'"),
+      TokenType::Enum => String::from("'enum'"),
+      TokenType::Extern => String::from("'extern'"),
+      TokenType::Float => String::from("'float'"),
+      TokenType::For => String::from("'for'"),
+      TokenType::Goto => String::from("'goto'"),
+      TokenType::If => String::from("'if'"),
+      TokenType::Inline => String::from("'inline'"),
+      TokenType::Int => String::from("'int'"),
+      TokenType::Long => String::from("'long'"),
+      TokenType::Register => String::from("'register'"),
+      TokenType::Restrict => String::from("'restrict'"),
+      TokenType::Return => String::from("'return'"),
+      TokenType::Short => String::from("'short'"),
+      TokenType::Signed => String::from("'signed'"),
+      TokenType::Sizeof => String::from("'sizeof'"),
+      TokenType::Static => String::from("'static'"),
+      TokenType::Struct => String::from("'struct'"),
+      TokenType::Switch => String::from("'switch'"),
+      TokenType::Typedef => String::from("'typedef'"),
+      TokenType::Union => String::from("'union'"),
+      TokenType::Unsigned => String::from("'unsigned'"),
+      TokenType::Void => String::from("'void'"),
+      TokenType::Volatile => String::from("'volatile'"),
+      TokenType::While => String::from("'while'"),
+      TokenType::Alignas => String::from("'_Alignas'"),
+      TokenType::Alignof => String::from("'_Alignof'"),
+      TokenType::Atomic => String::from("'_Atomic'"),
+      TokenType::Bool => String::from("'_Bool'"),
+      TokenType::Complex => String::from("'_Complex'"),
+      TokenType::Generic => String::from("'_Generic'"),
+      TokenType::Imaginary => String::from("'_Imaginary'"),
+      TokenType::Noreturn => String::from("'_Noreturn'"),
+      TokenType::StaticAssert => String::from("'_Static_assert'"),
+      TokenType::ThreadLocal => String::from("'_Thread_local'"),
       TokenType::Plus => String::from("'+'"),
       TokenType::Minus => String::from("'-'"),
       TokenType::Aster => String::from("'*'"),
@@ -56,8 +165,8 @@ impl ToSimpleString for TokenType {
       TokenType::LBrace => String::from("'{'"),
       TokenType::RBrace => String::from("'}'"),
       TokenType::Assign => String::from("'='"),
-      TokenType::Inc => String::from("'+='"),
-      TokenType::Dec => String::from("'-='"),
+      TokenType::AddAssign => String::from("'+='"),
+      TokenType::SubAssign => String::from("'-='"),
       TokenType::Not => String::from("'!'"),
       TokenType::Eq => String::from("'=='"),
       TokenType::Ne => String::from("'!='"),
@@ -67,11 +176,192 @@ impl ToSimpleString for TokenType {
       TokenType::Ge => String::from("'>='"),
       TokenType::Semicolon => String::from("';'"),
       TokenType::Comma => String::from("','"),
-      TokenType::Eof => String::from("<EOF>"),
+      TokenType::Percent => String::from("'%'"),
+      TokenType::Amp => String::from("'&'"),
+      TokenType::Pipe => String::from("'|'"),
+      TokenType::Caret => String::from("'^'"),
+      TokenType::Tilde => String::from("'~'"),
+      TokenType::Shl => String::from("'<<'"),
+      TokenType::Shr => String::from("'>>'"),
+      TokenType::AndAnd => String::from("'&&'"),
+      TokenType::OrOr => String::from("'||'"),
+      TokenType::Inc => String::from("'++'"),
+      TokenType::Dec => String::from("'--'"),
+      TokenType::Arrow => String::from("'->'"),
+      TokenType::Dot => String::from("'.'"),
+      TokenType::Question => String::from("'?'"),
+      TokenType::Colon => String::from("':'"),
+      TokenType::LBracket => String::from("'['"),
+      TokenType::RBracket => String::from("']'"),
+      TokenType::Ellipsis => String::from("'...'"),
+      TokenType::MulAssign => String::from("'*='"),
+      TokenType::DivAssign => String::from("'/='"),
+      TokenType::ModAssign => String::from("'%='"),
+      TokenType::AndAssign => String::from("'&='"),
+      TokenType::OrAssign => String::from("'|='"),
+      TokenType::XorAssign => String::from("'^='"),
+      TokenType::ShlAssign => String::from("'<<='"),
+      TokenType::ShrAssign => String::from("'>>='"),
     }
   }
 }
 
+/// Escapes the bytes of a string literal for printing.
+pub fn escape_bytes(bytes: &[u8]) -> String {
+  bytes
+    .iter()
+    .map(|b| (*b as char).escape_default().to_string())
+    .collect()
+}
+
+impl TokenType {
+  /// The length of the longest punctuator, which bounds the lookahead of the
+  /// maximal munch in the lexer.
+  pub const MAX_PUNCTUATOR_LEN: usize = 3;
+
+  /// Returns the token type of the punctuator `s`, if `s` is one.
+  pub fn punctuator(s: &str) -> Option<TokenType> {
+    let ty = match s {
+      "+" => TokenType::Plus,
+      "-" => TokenType::Minus,
+      "*" => TokenType::Aster,
+      "/" => TokenType::Slash,
+      "(" => TokenType::LParen,
+      ")" => TokenType::RParen,
+      "{" => TokenType::LBrace,
+      "}" => TokenType::RBrace,
+      "=" => TokenType::Assign,
+      "+=" => TokenType::AddAssign,
+      "-=" => TokenType::SubAssign,
+      "!" => TokenType::Not,
+      "==" => TokenType::Eq,
+      "!=" => TokenType::Ne,
+      "<" => TokenType::Lt,
+      ">" => TokenType::Gt,
+      "<=" => TokenType::Le,
+      ">=" => TokenType::Ge,
+      ";" => TokenType::Semicolon,
+      "," => TokenType::Comma,
+      "%" => TokenType::Percent,
+      "&" => TokenType::Amp,
+      "|" => TokenType::Pipe,
+      "^" => TokenType::Caret,
+      "~" => TokenType::Tilde,
+      "<<" => TokenType::Shl,
+      ">>" => TokenType::Shr,
+      "&&" => TokenType::AndAnd,
+      "||" => TokenType::OrOr,
+      "++" => TokenType::Inc,
+      "--" => TokenType::Dec,
+      "->" => TokenType::Arrow,
+      "." => TokenType::Dot,
+      "?" => TokenType::Question,
+      ":" => TokenType::Colon,
+      "[" => TokenType::LBracket,
+      "]" => TokenType::RBracket,
+      "..." => TokenType::Ellipsis,
+      "*=" => TokenType::MulAssign,
+      "/=" => TokenType::DivAssign,
+      "%=" => TokenType::ModAssign,
+      "&=" => TokenType::AndAssign,
+      "|=" => TokenType::OrAssign,
+      "^=" => TokenType::XorAssign,
+      "<<=" => TokenType::ShlAssign,
+      ">>=" => TokenType::ShrAssign,
+      _ => return None,
+    };
+    Some(ty)
+  }
+
+  /// Returns the token type of the keyword `s`, if `s` is one.
+  pub fn keyword(s: &str) -> Option<TokenType> {
+    let ty = match s {
+      "auto" => TokenType::Auto,
+      "break" => TokenType::Break,
+      "case" => TokenType::Case,
+      "char" => TokenType::CharKw,
+      "const" => TokenType::Const,
+      "continue" => TokenType::Continue,
+      "default" => TokenType::Default,
+      "do" => TokenType::Do,
+      "double" => TokenType::Double,
+      "else" => TokenType::Else,
+      "enum" => TokenType::Enum,
+      "extern" => TokenType::Extern,
+      "float" => TokenType::Float,
+      "for" => TokenType::For,
+      "goto" => TokenType::Goto,
+      "if" => TokenType::If,
+      "inline" => TokenType::Inline,
+      "int" => TokenType::Int,
+      "long" => TokenType::Long,
+      "register" => TokenType::Register,
+      "restrict" => TokenType::Restrict,
+      "return" => TokenType::Return,
+      "short" => TokenType::Short,
+      "signed" => TokenType::Signed,
+      "sizeof" => TokenType::Sizeof,
+      "static" => TokenType::Static,
+      "struct" => TokenType::Struct,
+      "switch" => TokenType::Switch,
+      "typedef" => TokenType::Typedef,
+      "union" => TokenType::Union,
+      "unsigned" => TokenType::Unsigned,
+      "void" => TokenType::Void,
+      "volatile" => TokenType::Volatile,
+      "while" => TokenType::While,
+      "_Alignas" => TokenType::Alignas,
+      "_Alignof" => TokenType::Alignof,
+      "_Atomic" => TokenType::Atomic,
+      "_Bool" => TokenType::Bool,
+      "_Complex" => TokenType::Complex,
+      "_Generic" => TokenType::Generic,
+      "_Imaginary" => TokenType::Imaginary,
+      "_Noreturn" => TokenType::Noreturn,
+      "_Static_assert" => TokenType::StaticAssert,
+      "_Thread_local" => TokenType::ThreadLocal,
+      _ => return None,
+    };
+    Some(ty)
+  }
+
+  /// Whether the token can begin the declaration specifiers of a declaration,
+  /// i.e. it is a type specifier, a type qualifier or a storage class.
+  pub fn is_decl_specifier(&self) -> bool {
+    matches!(
+      self,
+      TokenType::Auto
+        | TokenType::CharKw
+        | TokenType::Const
+        | TokenType::Double
+        | TokenType::Enum
+        | TokenType::Extern
+        | TokenType::Float
+        | TokenType::Inline
+        | TokenType::Int
+        | TokenType::Long
+        | TokenType::Register
+        | TokenType::Restrict
+        | TokenType::Short
+        | TokenType::Signed
+        | TokenType::Static
+        | TokenType::Struct
+        | TokenType::Typedef
+        | TokenType::Union
+        | TokenType::Unsigned
+        | TokenType::Void
+        | TokenType::Volatile
+        | TokenType::Alignas
+        | TokenType::Atomic
+        | TokenType::Bool
+        | TokenType::Complex
+        | TokenType::Imaginary
+        | TokenType::Noreturn
+        | TokenType::ThreadLocal
+    )
+  }
+}
+
 impl fmt::Display for TokenType {
   fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
     write!(f, "{}", self.to_simple_string())
@@ -86,16 +376,13 @@ impl fmt::Display for TokenType {
 
 #[derive(Debug, Clone, Copy)]
 pub struct Position {
-  start: usize,
-  end: usize,
+  pub start: usize,
+  pub end: usize,
 }
 
 impl Position {
   pub fn new(start: usize, end: usize) -> Self {
-    Self {
-      start: start,
-      end: end,
-    }
+    Self { start, end }
   }
 }
 
@@ -130,42 +417,29 @@ impl fmt::Display for Token {
 }
 
 impl Token {
-  pub fn num(n: u64, start: usize, end: usize) -> Self {
+  pub fn num(n: u64, typ: Type, position: Position) -> Self {
     Self {
-      ty: TokenType::Number(n),
-      position: Position::new(start, end),
+      ty: TokenType::Number(n, typ),
+      position,
     }
   }
 
-  pub fn id(s: String, start: usize, end: usize) -> Self {
+  pub fn id(s: String, position: Position) -> Self {
     Self {
       ty: TokenType::Id(s),
-      position: Position::new(start, end),
-    }
-  }
-
-  pub fn typ(s: &str, start: usize, end: usize) -> Self {
-    Self {
-      ty: TokenType::Type(String::from(s)),
-      position: Position::new(start, end),
+      position,
     }
   }
 
-  pub fn eof(start: usize) -> Self {
-    Self {
-      ty: TokenType::Eof,
-      position: Position::new(start, start),
-    }
-  }
-
-  pub fn get_id_string(&self) -> String {
+  pub fn get_id_string(&self) -> Option<String> {
     match &self.ty {
-      TokenType::Id(name) => name.clone(),
-      _ => panic!("Expect ID token but {} found...", self),
+      TokenType::Id(name) => Some(name.clone()),
+      _ => None,
     }
   }
 }
 
+#[derive(Debug)]
 pub struct TokenList {
   pub tokens: Vec<Token>,
 }
@@ -193,7 +467,7 @@ impl ToSimpleString for TokenList {
       }
       s.push_str(&format!("{}", token));
     }
-    s.push_str("]");
+    s.push(']');
     s
   }
 }
@@ -206,13 +480,13 @@ impl fmt::Display for TokenList {
 
 impl Index<usize> for TokenList {
   type Output = Token;
-  fn index<'a>(&'a self, i: usize) -> &'a Token {
+  fn index(&self, i: usize) -> &Token {
     &self.tokens[i]
   }
 }
 
 impl IndexMut<usize> for TokenList {
-  fn index_mut<'a>(&'a mut self, i: usize) -> &'a mut Token {
+  fn index_mut(&mut self, i: usize) -> &mut Token {
     &mut self.tokens[i]
   }
 }
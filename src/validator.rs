@@ -1,28 +1,374 @@
-use crate::ast::{Module, Stmt};
+use crate::ast::{ArgList, Declarator, Expr, Module, Stmt, Type};
+use crate::diagnostic::Diagnostic;
+use crate::layout::X86_64_SYSV;
+#[cfg(test)]
+use crate::lexer::Lexer;
+#[cfg(test)]
+use crate::parser::Parser;
+use crate::token::Position;
+use std::collections::HashMap;
 
-struct ModuleValidator {
-  module: Box<Module>,
+/// The labels seen so far in a switch body.
+struct SwitchScope {
+  // the promoted type of the controlling expression
+  typ: Type,
+  cases: Vec<(i64, Position)>,
+  default: Option<Position>,
 }
 
-impl ModuleValidator {
-  pub fn new(module: Box<Module>) -> Self {
-    ModuleValidator { module: module }
+/// A function declared so far in the module.
+struct FnSignature<'a> {
+  args: &'a ArgList,
+  ret_type: &'a Type,
+  // whether a declaration with a body has been seen
+  defined: bool,
+  position: Position,
+}
+
+/// Checks the rules the grammar can't express, e.g. that a `break` is inside
+/// a loop, on a parsed module. All violations are collected instead of
+/// stopping at the first one.
+pub struct ModuleValidator<'a> {
+  module: &'a Module,
+  errors: Vec<Diagnostic>,
+  // the number of loops enclosing the statement being checked
+  loop_depth: usize,
+  switch_stack: Vec<SwitchScope>,
+  // labels defined and referenced by 'goto' in the current function, which
+  // are checked at its end since a 'goto' may jump forward
+  labels: HashMap<String, Position>,
+  gotos: Vec<(String, Position)>,
+  functions: HashMap<&'a str, FnSignature<'a>>,
+}
+
+impl<'a> ModuleValidator<'a> {
+  pub fn new(module: &'a Module) -> Self {
+    ModuleValidator {
+      module,
+      errors: Vec::new(),
+      loop_depth: 0,
+      switch_stack: Vec::new(),
+      labels: HashMap::new(),
+      gotos: Vec::new(),
+      functions: HashMap::new(),
+    }
   }
 
-  pub fn check(&self) {
-    let mut main_found = false;
-    for stmt in self.module.stmt_list {
+  pub fn check(mut self) -> Vec<Diagnostic> {
+    for stmt in self.module.stmt_list.iter() {
       match stmt {
-        Stmt::FnStmt { name, args, body } => {
-          if name == "main" {
-            main_found = true;
+        Stmt::VarDecl { decls, .. } => self.check_global(decls),
+        Stmt::FnDecl {
+          name,
+          args,
+          ret_type,
+          position,
+        } => self.check_fn_signature(name, args, ret_type, *position, false),
+        Stmt::FnStmt {
+          name,
+          args,
+          ret_type,
+          position,
+          ..
+        } => {
+          self.check_fn_signature(name, args, ret_type, *position, true);
+          self.check_stmt(stmt);
+        }
+        _ => self.check_stmt(stmt),
+      }
+    }
+    self.errors
+  }
+
+  /// File-scope variables are initialized by the assembler, so their
+  /// initializers must be constants.
+  fn check_global(&mut self, decls: &[Declarator]) {
+    for init in decls.iter().filter_map(|decl| decl.init.as_ref()) {
+      if init.const_value().is_none() {
+        self.errors.push(Diagnostic::error(
+          "initializer element is not a compile-time constant",
+          init.position(),
+        ));
+      }
+    }
+  }
+
+  /// Checks a function declaration against the earlier ones of the same
+  /// name, which must all agree on the types.
+  fn check_fn_signature(
+    &mut self,
+    name: &'a str,
+    args: &'a ArgList,
+    ret_type: &'a Type,
+    position: Position,
+    defined: bool,
+  ) {
+    if defined {
+      for arg in args.container.iter().filter(|arg| arg.name.is_empty()) {
+        self
+          .errors
+          .push(Diagnostic::error("parameter name omitted", arg.position));
+      }
+    }
+    let previous = match self.functions.get_mut(name) {
+      Some(previous) => previous,
+      None => {
+        self.functions.insert(
+          name,
+          FnSignature {
+            args,
+            ret_type,
+            defined,
+            position,
+          },
+        );
+        return;
+      }
+    };
+    if defined && previous.defined {
+      self.errors.push(
+        Diagnostic::error(format!("redefinition of '{}'", name), position)
+          .with_label(previous.position, "previous definition is here"),
+      );
+    } else if previous.ret_type != ret_type || !previous.args.same_types(args) {
+      self.errors.push(
+        Diagnostic::error(format!("conflicting types for '{}'", name), position)
+          .with_label(previous.position, "previous declaration is here"),
+      );
+    }
+    if defined && !previous.defined {
+      previous.defined = true;
+      previous.position = position;
+    }
+  }
+
+  fn check_block(&mut self, body: &[Stmt]) {
+    for stmt in body {
+      self.check_stmt(stmt);
+    }
+  }
+
+  fn check_loop_body(&mut self, body: &Stmt) {
+    self.loop_depth += 1;
+    self.check_stmt(body);
+    self.loop_depth -= 1;
+  }
+
+  fn check_stmt(&mut self, stmt: &Stmt) {
+    match stmt {
+      Stmt::ExprStmt { .. }
+      | Stmt::VarDecl { .. }
+      | Stmt::FnDecl { .. }
+      | Stmt::Empty { .. }
+      | Stmt::ReturnStmt { .. } => {}
+      Stmt::Block { body, .. } => self.check_block(body),
+      Stmt::IfStmt {
+        true_body,
+        false_body,
+        ..
+      } => {
+        self.check_stmt(true_body);
+        if let Some(body) = false_body {
+          self.check_stmt(body);
+        }
+      }
+      Stmt::ForStmt { body, .. }
+      | Stmt::WhileStmt { body, .. }
+      | Stmt::DoWhileStmt { body, .. } => self.check_loop_body(body),
+      Stmt::SwitchStmt { typ, body, .. } => {
+        self.switch_stack.push(SwitchScope {
+          typ: typ.clone(),
+          cases: Vec::new(),
+          default: None,
+        });
+        self.check_stmt(body);
+        self.switch_stack.pop();
+      }
+      Stmt::CaseStmt {
+        value,
+        body,
+        position,
+      } => {
+        self.check_case(value, *position);
+        self.check_stmt(body);
+      }
+      Stmt::DefaultStmt { body, position } => {
+        self.check_default(*position);
+        self.check_stmt(body);
+      }
+      Stmt::BreakStmt { position } => {
+        if self.loop_depth == 0 && self.switch_stack.is_empty() {
+          self.errors.push(Diagnostic::error(
+            "'break' statement not in loop or switch statement",
+            *position,
+          ));
+        }
+      }
+      Stmt::ContinueStmt { position } => {
+        if self.loop_depth == 0 {
+          self.errors.push(Diagnostic::error(
+            "'continue' statement not in loop statement",
+            *position,
+          ));
+        }
+      }
+      Stmt::LabelStmt {
+        name,
+        body,
+        position,
+      } => {
+        match self.labels.get(name) {
+          Some(previous) => self.errors.push(
+            Diagnostic::error(format!("redefinition of label '{}'", name), *position)
+              .with_label(*previous, "previous definition is here"),
+          ),
+          None => {
+            self.labels.insert(name.clone(), *position);
+          }
+        }
+        self.check_stmt(body);
+      }
+      Stmt::GotoStmt { name, position } => self.gotos.push((name.clone(), *position)),
+      Stmt::FnStmt { body, .. } => {
+        self.check_block(body);
+        for (name, position) in std::mem::take(&mut self.gotos) {
+          if !self.labels.contains_key(&name) {
+            self.errors.push(Diagnostic::error(
+              format!("use of undeclared label '{}'", name),
+              position,
+            ));
           }
         }
-        _ => panic!("currently FnStmt is only supported..."),
+        self.labels.clear();
       }
     }
-    if !main_found {
-      panic!("'main' function not found in the module");
+  }
+
+  fn check_case(&mut self, value: &Expr, position: Position) {
+    let scope = match self.switch_stack.last_mut() {
+      Some(scope) => scope,
+      None => {
+        self.errors.push(Diagnostic::error(
+          "'case' statement not in switch statement",
+          position,
+        ));
+        return;
+      }
+    };
+    let value_position = value.position();
+    // the values are compared as the controlling expression, so '-1' and
+    // '4294967295u' are the same in an 'unsigned int' switch
+    let value = match value.const_value() {
+      Some(value) => X86_64_SYSV.convert(value, &scope.typ),
+      None => {
+        self.errors.push(Diagnostic::error(
+          "expression is not an integer constant expression",
+          value_position,
+        ));
+        return;
+      }
+    };
+    match scope.cases.iter().find(|(v, _)| *v == value) {
+      Some((_, previous)) => self.errors.push(
+        Diagnostic::error(format!("duplicate case value '{}'", value), value_position)
+          .with_label(*previous, "previous case defined here"),
+      ),
+      None => scope.cases.push((value, value_position)),
+    }
+  }
+
+  fn check_default(&mut self, position: Position) {
+    let scope = match self.switch_stack.last_mut() {
+      Some(scope) => scope,
+      None => {
+        self.errors.push(Diagnostic::error(
+          "'default' statement not in switch statement",
+          position,
+        ));
+        return;
+      }
+    };
+    match scope.default {
+      Some(previous) => self.errors.push(
+        Diagnostic::error("multiple default labels in one switch", position)
+          .with_label(previous, "previous case defined here"),
+      ),
+      None => scope.default = Some(position),
     }
   }
 }
+
+#[test]
+fn test_validator() {
+  test_validate(
+    "int main() { while (1) { if (1) { break; } continue; } return 0; }",
+    vec![],
+  );
+  test_validate(
+    "int main() { break; if (1) { continue; } }",
+    vec![
+      "error@[13,18]: 'break' statement not in loop or switch statement",
+      "error@[29,37]: 'continue' statement not in loop statement",
+    ],
+  );
+  test_validate(
+    "int main() { switch (1) { case 1: break; default: while (1) { case 2: continue; } } }",
+    vec![],
+  );
+  test_validate(
+    "int f() { goto b; a: goto a; } int g() { b: a: if (1) { a: ; } goto c; }",
+    vec![
+      "error@[15,16]: use of undeclared label 'b'",
+      "error@[56,57]: redefinition of label 'a'\n  note@[44,45]: previous definition is here",
+      "error@[68,69]: use of undeclared label 'c'",
+    ],
+  );
+  test_validate(
+    "int f(int x) { case 0: switch (x) { case 1+1: case x: case 2: default: default: ; } }",
+    vec![
+      "error@[15,19]: 'case' statement not in switch statement",
+      "error@[51,52]: expression is not an integer constant expression",
+      "error@[59,60]: duplicate case value '2'\n  note@[42,43]: previous case defined here",
+      "error@[71,78]: multiple default labels in one switch\n  note@[62,69]: previous case defined here",
+    ],
+  );
+  test_validate(
+    "int a = 1 << 4, b; int c = a + 1; int f() { int d = a; }",
+    vec!["error@[29,30]: initializer element is not a compile-time constant"],
+  );
+  test_validate(
+    "int f(int, int); int f(int a, int b) { return a; } int f(int x, int y); \
+     int g(void); int g(int a); int h(int a, ...); int h(int a) { return a; } \
+     int f(int a, int b) { return b; } int k(int) { return 0; }",
+    vec![
+      "error@[89,90]: conflicting types for 'g'\n  note@[76,77]: previous declaration is here",
+      "error@[122,123]: conflicting types for 'h'\n  note@[103,104]: previous declaration is here",
+      "error@[149,150]: redefinition of 'f'\n  note@[21,22]: previous definition is here",
+      "error@[185,188]: parameter name omitted",
+    ],
+  );
+  test_validate(
+    "int a[sizeof(int [2][3]) / _Alignof(int)]; int b = sizeof \"abc\" + sizeof 1; \
+     int f(int x) { switch (x) { case sizeof(char *): case 8: case sizeof 'a': ; } }",
+    vec!["error@[130,131]: duplicate case value '8'\n  note@[109,115]: previous case defined here"],
+  );
+  test_validate(
+    "int f(unsigned x, char c) { switch (x) { case -1: case 4294967295u: ; } \
+     switch (c) { case 255: case -1: ; } }",
+    vec!["error@[55,66]: duplicate case value '4294967295'\n  note@[46,47]: previous case defined here"],
+  );
+}
+
+#[cfg(test)]
+fn test_validate(input: &str, expected: Vec<&str>) {
+  let mut lexer = Lexer::new(input.chars().collect());
+  let mut parser = Parser::new(lexer.tokenize().unwrap());
+  let (module, errors) = parser.parse();
+  assert!(errors.is_empty());
+  let errors: Vec<String> = ModuleValidator::new(&module)
+    .check()
+    .iter()
+    .map(|diag| format!("{}", diag))
+    .collect();
+  assert_eq!(errors, expected);
+}